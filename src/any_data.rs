@@ -24,6 +24,10 @@ impl AnyData {
         self.data.downcast_ref().expect(NOT_MATCH_MSG)
     }
 
+    pub fn try_cast_ref<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+
     pub fn cast_mut<T: Any>(&mut self) -> &mut T {
         self.data.downcast_mut().expect(NOT_MATCH_MSG)
     }
@@ -1,13 +1,29 @@
 use crate::any_data::AnyData;
-use crate::common_vec_op::{CodeParser, CommonVecVisualizer, VecLineGen};
+use crate::common_vec_op::{
+    BooleanOp, CodeParser, CodeTransform, CommonVecVisualizer, CurveAnalysis,
+    DEFAULT_FORMAT_PRECISION, Easing, FixedPoint, HitTest, Intersection, Measure, MorphError,
+    PlotInput, QuadApproximation, VecLineData, VecLineGen, append_commands, bake_transforms,
+    boolean_code, boolean_path, convert_conics, cubics_to_quads, elevate_quads, fit_curves,
+    fit_points, fmt_num, format_code, morph_code, rasterize, replace_spans, reveal_lines,
+    simplify_lines, transform_code,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::common_vec_op::{
+    ExportError, ExportFormat, ExportOptions, MorphExport, Sweep, export_frames,
+};
 use crate::cus_component::{CodeEditor, toggle};
 use crate::interfaces::{
-    ICodeEditor, IParser, IVisData, IVisDataGenerator, IVisualizer, ParseError,
+    Cursor, Diagnostic, DiagnosticLevel, ICodeEditor, IParser, IVisData, IVisDataGenerator,
+    IVisualizer, ParseError, QuickFix,
 };
 use bincode::{Decode, Encode};
 use eframe::{Storage, egui};
 use log::error;
 use std::collections::{BTreeMap, BTreeSet};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::time::Duration;
 use std::vec;
 
@@ -15,20 +31,53 @@ use super::sample_codes_list::SAMPLE_CODES_LIST;
 use crate::egui::Sense;
 use base64::prelude::*;
 
-const WINDOW_NAMES: [[&str; 2]; 6] = [
-    ["🐑", "Samples"],
+const SAMPLES_PANEL: &str = "Samples";
+const OPTIONS_PANEL: &str = "Options";
+const TRANSFORM_PANEL: &str = "Transform";
+const CODE_PANEL: &str = "Code";
+const DEVICE_PANEL: &str = "Device";
+const MEASURE_PANEL: &str = "Measure";
+const INTERSECTIONS_PANEL: &str = "Intersections";
+const HIT_TEST_PANEL: &str = "Hit Test";
+const MORPH_PANEL: &str = "Morph";
+const ABOUT_PANEL: &str = "About";
+
+const WINDOW_NAMES: [[&str; 2]; 11] = [
+    ["🐑", SAMPLES_PANEL],
     ["", ""],
-    ["⚙", "Options"],
-    ["🔢", "Transform"],
-    ["📄", "Code"],
-    ["ℹ", "About"],
+    ["⚙", OPTIONS_PANEL],
+    ["🔢", TRANSFORM_PANEL],
+    ["📄", CODE_PANEL],
+    ["📟", DEVICE_PANEL],
+    ["📏", MEASURE_PANEL],
+    ["✖", INTERSECTIONS_PANEL],
+    ["🎯", HIT_TEST_PANEL],
+    ["🎞", MORPH_PANEL],
+    ["ℹ", ABOUT_PANEL],
 ];
 
 struct MainAppCache {
     code: AnyData,
     lines: Vec<Vec<Box<dyn IVisData>>>,
+    /// lines with fixed point coordinates and their max distance from the exact ones
+    quantized_lines: Vec<Vec<Box<dyn IVisData>>>,
+    max_deviation: f64,
+    /// geometry of every subpath drawn, None while the measure panel is closed
+    measures: Option<Vec<Measure>>,
+    /// combs and marks on the curves, None if not asked for
+    analysis: Option<CurveAnalysis>,
+    /// where the drawn segments cross each other, None while the intersections panel is closed
+    intersections: Option<Vec<Intersection>>,
+    /// the hit test point against the drawn paths
+    hit_test: Option<HitTest>,
+    /// quads the cubics would be converted into, None if not asked for
+    quad_approximation: Option<QuadApproximation>,
+    /// the parsed code, kept to evaluate the timed commands again every frame
+    generator: VecLineGen,
+    time: f64,
 
     params: MainAppParams,
+    morph_target: String,
 
     #[cfg(target_arch = "wasm32")]
     transfer_data: TransferData,
@@ -36,34 +85,106 @@ struct MainAppCache {
 
 #[derive(Clone, PartialEq, Decode, Encode)]
 struct MainAppParams {
-    vis_progress_anim: bool,
-    /// true: positive, false: negative
-    vis_progress_anim_dir: bool,
     vis_progress: i64,
     vis_progress_max: i64,
     lcd_coords: bool,
     show_inter_dash: bool,
     colorful_block: bool,
+    check_canvas: bool,
+    show_canvas: bool,
+    /// x, y, w, h of the screen the path is drawn on
+    canvas: [f64; 4],
+    fixed_point: bool,
+    /// integer bits with the sign, fraction bits
+    fixed_point_bits: [u32; 2],
+    /// pixels of the device preview framebuffer
+    device_size: [u32; 2],
+    device_zoom: u32,
+    curve_analysis: bool,
+    /// where the tangents and normals are shown
+    analysis_t: f64,
+    comb_scale: f64,
+    /// max distance in drawn coordinates between a cubic and the quads replacing it
+    quad_tolerance: f64,
+    show_quad_error: bool,
+    /// draw the code moved toward the morph target by the progress
+    morph: bool,
+    /// index into `Easing::ALL`
+    morph_easing: usize,
+    /// start over at the end of the timeline
+    time_loop: bool,
+    /// seconds of `t` per second
+    time_speed: f64,
+    /// seconds of the timeline
+    time_duration: f64,
+    /// reveal the paths along their length over the timeline
+    pen_reveal: bool,
 
     trans_matrix: [[f64; 3]; 3],
 }
 
+/// Bumped whenever the layout of `TransferData` changes, links of other versions only keep their code
+const TRANSFER_VERSION: u32 = 2;
+
+/// Encoded into shared URLs as `?{TRANSFER_VERSION}.{base64}`, the code stays the first field
 #[derive(Clone, PartialEq, Default, Decode, Encode)]
 struct TransferData {
     code: String,
     params: Option<MainAppParams>,
 }
 
+impl MainAppParams {
+    /// Whether the code parses the same, the morph and the canvas lint are done while parsing
+    fn parses_as(&self, other: &Self) -> bool {
+        self.morph == other.morph
+            && self.morph_easing == other.morph_easing
+            && self.check_canvas == other.check_canvas
+            && self.canvas == other.canvas
+    }
+
+    /// Whether the parsed code draws the same lines
+    fn draws_as(&self, other: &Self) -> bool {
+        self.vis_progress == other.vis_progress
+            && self.fixed_point == other.fixed_point
+            && self.fixed_point_bits == other.fixed_point_bits
+    }
+
+    /// Whether the combs and the quad error shown on the lines stay the same
+    fn analyzes_as(&self, other: &Self) -> bool {
+        self.curve_analysis == other.curve_analysis
+            && self.analysis_t == other.analysis_t
+            && self.comb_scale == other.comb_scale
+            && self.show_quad_error == other.show_quad_error
+            && self.quad_tolerance == other.quad_tolerance
+    }
+}
+
 impl Default for MainAppParams {
     fn default() -> Self {
         Self {
-            vis_progress_anim: false,
-            vis_progress_anim_dir: true,
             vis_progress: 0,
             vis_progress_max: 0,
             lcd_coords: false,
             show_inter_dash: true,
             colorful_block: true,
+            check_canvas: false,
+            show_canvas: false,
+            canvas: [0.0, 0.0, 480.0, 320.0],
+            fixed_point: false,
+            fixed_point_bits: [16, 16],
+            device_size: [320, 240],
+            device_zoom: 2,
+            curve_analysis: false,
+            analysis_t: 0.5,
+            comb_scale: 1.0,
+            quad_tolerance: 0.1,
+            show_quad_error: false,
+            morph: false,
+            morph_easing: 0,
+            time_loop: true,
+            time_speed: 1.0,
+            time_duration: 2.0,
+            pen_reveal: false,
             trans_matrix: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], // Identity matrix
         }
     }
@@ -74,7 +195,17 @@ impl Default for MainAppCache {
         Self {
             code: AnyData::new("".to_owned()),
             lines: vec![],
+            quantized_lines: vec![],
+            max_deviation: 0.0,
+            measures: None,
+            analysis: None,
+            intersections: None,
+            hit_test: None,
+            quad_approximation: None,
+            generator: VecLineGen::default(),
+            time: 0.0,
             params: Default::default(),
+            morph_target: String::new(),
 
             #[cfg(target_arch = "wasm32")]
             transfer_data: Default::default(),
@@ -82,11 +213,139 @@ impl Default for MainAppCache {
     }
 }
 
+/// Inputs of the refactorings in the code editor
+struct RefactorParams {
+    /// x, y, w, h
+    fit_box: [f64; 4],
+    scale: f64,
+    decimals: usize,
+    grid: f64,
+    /// max distance of the simplified lines and fitted curves from the points
+    simplify_tolerance: f64,
+    /// the two shapes to combine
+    boolean_shapes: [usize; 2],
+    boolean_op: BooleanOp,
+}
+
+impl Default for RefactorParams {
+    fn default() -> Self {
+        Self {
+            fit_box: [0.0, 0.0, 240.0, 240.0],
+            scale: 2.0,
+            decimals: 0,
+            grid: 1.0,
+            simplify_tolerance: 0.5,
+            boolean_shapes: [0, 1],
+            boolean_op: BooleanOp::Union,
+        }
+    }
+}
+
+/// The animation export, only on native where files can be written
+#[cfg(not(target_arch = "wasm32"))]
+struct ExportParams {
+    options: ExportOptions,
+    path: String,
+    /// what the last export did
+    status: String,
+    job: Option<ExportJob>,
+}
+
+/// An export running on its own thread
+#[cfg(not(target_arch = "wasm32"))]
+struct ExportJob {
+    handle: std::thread::JoinHandle<Result<usize, ExportError>>,
+    /// count of frames written
+    progress: Arc<AtomicUsize>,
+    cancel: Arc<AtomicBool>,
+    frames: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for ExportParams {
+    fn default() -> Self {
+        Self {
+            options: ExportOptions::default(),
+            path: "vegravis.gif".to_owned(),
+            status: String::new(),
+            job: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+enum PenMode {
+    #[default]
+    Off,
+    /// click for a `line`, drag to pull out `cubi` handles
+    Pen,
+    /// strokes are fitted to curves
+    Freehand,
+}
+
+/// Drawing on the plot, in drawn coordinates
+struct PenState {
+    mode: PenMode,
+    /// max distance of the fitted curves from a freehand stroke
+    tolerance: f64,
+    /// first and last anchors of the path being drawn
+    anchors: Option<[[f64; 2]; 2]>,
+    /// control point going out of the last anchor
+    handle: Option<[f64; 2]>,
+    /// where the pen drag was pressed
+    drag_start: Option<[f64; 2]>,
+    stroke: Vec<[f64; 2]>,
+    pointer: Option<[f64; 2]>,
+    /// why the last input was not drawn
+    error: Option<String>,
+}
+
+impl Default for PenState {
+    fn default() -> Self {
+        Self {
+            mode: PenMode::Off,
+            tolerance: 1.0,
+            anchors: None,
+            handle: None,
+            drag_start: None,
+            stroke: vec![],
+            pointer: None,
+            error: None,
+        }
+    }
+}
+
+/// The params of the point drawn at `p`
+fn pen_point(p: [f64; 2], to_params: [[f64; 3]; 3]) -> [f64; 2] {
+    let p = VecLineData::new(p[0], p[1])
+        .matrix(to_params)
+        .cast::<VecLineData>();
+    [p.x(), p.y()]
+}
+
 pub struct MainApp {
     code: AnyData,
     error: Option<ParseError>,
+    /// the morph target doesn't parse, the cursor is in the target
+    morph_error: Option<ParseError>,
+    diagnostics: Vec<Diagnostic>,
 
     params: MainAppParams,
+    /// program the code morphs toward, left out of the shared URL
+    morph_target: String,
+    refactor: RefactorParams,
+    #[cfg(not(target_arch = "wasm32"))]
+    export: ExportParams,
+    /// rasterized lines of the device preview, dropped when the lines change
+    device_texture: Option<egui::TextureHandle>,
+    /// subpath shown in the measure panel, None for the whole program
+    measure_subpath: Option<usize>,
+    /// point clicked with the hit test window open, in drawn coordinates
+    hit_point: Option<[f64; 2]>,
+    pen: PenState,
+    /// `t` in the code, in seconds along the timeline
+    time: f64,
+    playing: bool,
 
     cache: MainAppCache,
     samples_cache: BTreeMap<&'static str, MainAppCache>,
@@ -106,17 +365,22 @@ impl Default for MainApp {
         let mut app = Self {
             code: AnyData::new(SAMPLE_CODES_LIST[0].1.to_owned()),
             params: MainAppParams::default(),
-            cache: MainAppCache {
-                code: AnyData::new("".to_owned()),
-                lines: vec![],
-                params: MainAppParams::default(),
-
-                #[cfg(target_arch = "wasm32")]
-                transfer_data: TransferData::default(),
-            },
+            morph_target: String::new(),
+            refactor: RefactorParams::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            export: ExportParams::default(),
+            device_texture: None,
+            measure_subpath: None,
+            hit_point: None,
+            pen: PenState::default(),
+            time: 0.0,
+            playing: false,
+            cache: MainAppCache::default(),
             samples_cache: Default::default(),
 
             error: None,
+            morph_error: None,
+            diagnostics: vec![],
 
             #[cfg(target_arch = "wasm32")]
             is_loaded_from_url: false,
@@ -127,7 +391,7 @@ impl Default for MainApp {
         };
 
         app.side_panel_open = true;
-        app.panel_status.insert(WINDOW_NAMES[2][1].to_owned());
+        app.panel_status.insert(OPTIONS_PANEL.to_owned());
 
         app
     }
@@ -141,26 +405,25 @@ impl eframe::App for MainApp {
             self.is_loaded_from_url = true;
         }
 
-        if !self.panel_status.contains(WINDOW_NAMES[0][1]) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.poll_export(ctx);
+
+        if !self.panel_status.contains(SAMPLES_PANEL) {
             self.selected_sample = "";
             self.hovered_sample = "";
         }
 
-        if self.params.vis_progress_anim {
+        if self.playing {
             ctx.request_repaint_after_secs(0.033);
 
-            self.params.vis_progress += if self.params.vis_progress_anim_dir {
-                1
-            } else {
-                -1
-            };
-
-            if self.params.vis_progress > self.params.vis_progress_max {
-                self.params.vis_progress_anim_dir = false;
-            }
-
-            if self.params.vis_progress < 0 {
-                self.params.vis_progress_anim_dir = true;
+            self.time += ctx.input(|i| i.stable_dt) as f64 * self.params.time_speed;
+            if self.time >= self.params.time_duration {
+                if self.params.time_loop {
+                    self.time %= self.params.time_duration;
+                } else {
+                    self.time = self.params.time_duration;
+                    self.playing = false;
+                }
             }
         }
 
@@ -179,63 +442,98 @@ impl eframe::App for MainApp {
 
         egui::SidePanel::left("Samples")
             .resizable(false)
-            .show_animated(ctx, self.panel_status.contains(WINDOW_NAMES[0][1]), |ui| {
+            .show_animated(ctx, self.panel_status.contains(SAMPLES_PANEL), |ui| {
                 self.ui_samples_panel(ui);
             });
 
         egui::TopBottomPanel::bottom("SampleCodeEditor")
             .resizable(false)
             .exact_height(ctx.available_rect().height() / 2.0)
-            .show_animated(ctx, self.panel_status.contains(WINDOW_NAMES[0][1]), |ui| {
+            .show_animated(ctx, self.panel_status.contains(SAMPLES_PANEL), |ui| {
                 self.ui_sample_code_editor(ui);
             });
 
         let options_resp = egui::TopBottomPanel::top("Options").show_animated(
             ctx,
-            self.panel_status.contains(WINDOW_NAMES[2][1]),
+            self.panel_status.contains(OPTIONS_PANEL),
             |ui| {
                 self.ui_options_panel(ui);
             },
         );
 
-        let mut transform_open = self.panel_status.contains(WINDOW_NAMES[3][1]);
-        egui::Window::new("Transform")
-            .title_bar(false)
-            .open(&mut transform_open)
-            .fixed_size([140.0, 120.0])
-            .anchor(
-                egui::Align2::RIGHT_TOP,
-                [
-                    0.0,
-                    about_resp.response.rect.height()
-                        + options_resp
-                            .map(|t| t.response.rect.height())
-                            .unwrap_or_default(),
-                ],
-            )
-            .show(ctx, |ui| {
-                self.ui_transform_panel(ui);
-            });
-        if !transform_open {
-            self.panel_status.remove(WINDOW_NAMES[3][1]);
-        }
+        let anchor_y = about_resp.response.rect.height()
+            + options_resp
+                .map(|t| t.response.rect.height())
+                .unwrap_or_default();
+        self.panel_window(
+            ctx,
+            TRANSFORM_PANEL,
+            |w| {
+                w.title_bar(false)
+                    .fixed_size([140.0, 120.0])
+                    .anchor(egui::Align2::RIGHT_TOP, [0.0, anchor_y])
+            },
+            Self::ui_transform_panel,
+        );
+
+        let right_bottom = ctx.screen_rect().right_bottom();
+        self.panel_window(
+            ctx,
+            MEASURE_PANEL,
+            |w| {
+                w.resizable(false)
+                    .default_pos(right_bottom - egui::vec2(260.0, 260.0))
+            },
+            Self::ui_measure_panel,
+        );
+        self.panel_window(
+            ctx,
+            INTERSECTIONS_PANEL,
+            |w| {
+                w.resizable(false)
+                    .default_pos(right_bottom - egui::vec2(520.0, 260.0))
+            },
+            Self::ui_intersections_panel,
+        );
+        self.panel_window(
+            ctx,
+            HIT_TEST_PANEL,
+            |w| {
+                w.resizable(false)
+                    .default_pos(right_bottom - egui::vec2(260.0, 520.0))
+            },
+            Self::ui_hit_test_panel,
+        );
+        self.panel_window(
+            ctx,
+            MORPH_PANEL,
+            |w| w.default_pos(right_bottom - egui::vec2(520.0, 520.0)),
+            Self::ui_morph_panel,
+        );
 
         if ctx.available_rect().aspect_ratio() < 1.0 {
             egui::TopBottomPanel::bottom("CodeEditor")
                 .resizable(false)
                 .exact_height(ctx.available_rect().height() / 2.0)
-                .show_animated(ctx, self.panel_status.contains(WINDOW_NAMES[4][1]), |ui| {
+                .show_animated(ctx, self.panel_status.contains(CODE_PANEL), |ui| {
                     self.ui_code_editor(ui);
                 });
         } else {
             egui::SidePanel::left("CodeEditor")
                 .resizable(false)
                 .exact_width(ctx.available_rect().width() / 2.0)
-                .show_animated(ctx, self.panel_status.contains(WINDOW_NAMES[4][1]), |ui| {
+                .show_animated(ctx, self.panel_status.contains(CODE_PANEL), |ui| {
                     self.ui_code_editor(ui);
                 });
         }
 
+        egui::SidePanel::right("Device")
+            .resizable(true)
+            .default_width(360.0)
+            .show_animated(ctx, self.panel_status.contains(DEVICE_PANEL), |ui| {
+                self.ui_device_preview(ui);
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             self.ui_visualizer(ui);
         });
@@ -252,22 +550,82 @@ impl eframe::App for MainApp {
 }
 
 impl MainApp {
+    /// Show the window of a panel while it is open, closing the window closes the panel
+    fn panel_window(
+        &mut self,
+        ctx: &egui::Context,
+        name: &str,
+        style: impl FnOnce(egui::Window) -> egui::Window,
+        add_contents: impl FnOnce(&mut Self, &mut egui::Ui),
+    ) {
+        let mut open = self.panel_status.contains(name);
+        style(egui::Window::new(name).open(&mut open)).show(ctx, |ui| add_contents(self, ui));
+        if !open {
+            self.panel_status.remove(name);
+        }
+    }
+
     fn ui_toast_bar(&mut self, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
-            ui.horizontal(|ui| {
-                let info = self.error.as_ref().map_or_else(
-                    || "".to_owned(),
-                    |e| format!("({}, {}): Error: {}", e.cursor.row + 1, e.cursor.col, e.msg),
-                );
-                let rt = egui::RichText::new(info)
-                    .size(20.0)
-                    .color(egui::Color32::RED)
-                    .text_style(egui::TextStyle::Monospace);
-                ui.label(rt).highlight();
-            });
+            if self.error.is_none() && self.morph_error.is_none() && self.diagnostics.is_empty() {
+                ui.horizontal(|ui| {
+                    let rt = egui::RichText::new("")
+                        .size(20.0)
+                        .text_style(egui::TextStyle::Monospace);
+                    ui.label(rt);
+                });
+                return;
+            }
+
+            let mut fix_to_apply = None;
+            egui::ScrollArea::vertical()
+                .max_height(100.0)
+                .show(ui, |ui| {
+                    if let Some(e) = &self.error {
+                        Self::ui_diagnostic(ui, DiagnosticLevel::Error, &e.cursor, &e.msg, None);
+                    }
+                    if let Some(e) = &self.morph_error {
+                        let msg = format!("In the morph target: {}", e.msg);
+                        Self::ui_diagnostic(ui, DiagnosticLevel::Error, &e.cursor, &msg, None);
+                    }
+                    for d in &self.diagnostics {
+                        if Self::ui_diagnostic(ui, d.level, &d.span.0, &d.msg, d.fix.as_ref()) {
+                            fix_to_apply = d.fix.clone();
+                        }
+                    }
+                });
+
+            if let Some(fix) = fix_to_apply {
+                let code = replace_spans(self.code.cast_ref::<String>(), fix.edits);
+                self.code = AnyData::new(code);
+            }
         });
     }
 
+    /// Returns true if the quick fix is clicked
+    fn ui_diagnostic(
+        ui: &mut egui::Ui,
+        level: DiagnosticLevel,
+        cursor: &Cursor,
+        msg: &str,
+        fix: Option<&QuickFix>,
+    ) -> bool {
+        ui.horizontal(|ui| {
+            let info = format!("({}, {}): {:?}: {}", cursor.row + 1, cursor.col, level, msg);
+            let rt = egui::RichText::new(info)
+                .size(20.0)
+                .color(match level {
+                    DiagnosticLevel::Error => egui::Color32::RED,
+                    DiagnosticLevel::Warning => egui::Color32::ORANGE,
+                })
+                .text_style(egui::TextStyle::Monospace);
+            ui.label(rt).highlight();
+
+            fix.is_some_and(|fix| ui.button(format!("🔧 {}", fix.title)).clicked())
+        })
+        .inner
+    }
+
     fn ui_panels(&mut self, ui: &mut egui::Ui) {
         egui::ScrollArea::vertical().show(ui, |ui| {
             ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
@@ -285,7 +643,7 @@ impl MainApp {
                     }
 
                     // only show the side panel when the screen is large
-                    if ui.ctx().screen_rect().width() > 600.0 && name == WINDOW_NAMES[5][1] {
+                    if ui.ctx().screen_rect().width() > 600.0 && name == ABOUT_PANEL {
                         continue;
                     }
 
@@ -444,7 +802,7 @@ impl MainApp {
         ui.with_layout(egui::Layout::left_to_right(egui::Align::TOP), |ui| {
             if ui.button("👆 Replaced With THIS 👇").clicked() {
                 self.code = sample_code.clone::<String>();
-                self.panel_status.remove(WINDOW_NAMES[0][1]);
+                self.panel_status.remove(SAMPLES_PANEL);
             }
             ui.shrink_height_to_current();
 
@@ -474,17 +832,6 @@ impl MainApp {
                 egui::Vec2::new(200.0, 20.0),
                 egui::Layout::left_to_right(egui::Align::Center),
                 |ui| {
-                    let mut anim_status = self.params.vis_progress_anim;
-                    ui.toggle_value(
-                        &mut anim_status,
-                        if self.params.vis_progress_anim {
-                            "⏸"
-                        } else {
-                            "▶"
-                        },
-                    );
-
-                    self.params.vis_progress_anim = anim_status;
                     ui.add(
                         egui::Slider::new(
                             &mut self.params.vis_progress,
@@ -495,15 +842,288 @@ impl MainApp {
                     );
                 },
             );
+            ui.allocate_ui_with_layout(
+                egui::Vec2::new(420.0, 20.0),
+                egui::Layout::left_to_right(egui::Align::Center),
+                |ui| {
+                    let mut playing = self.playing;
+                    ui.toggle_value(&mut playing, if self.playing { "⏸" } else { "▶" });
+                    // playing again from the end of the timeline starts over
+                    if playing && !self.playing && self.time >= self.params.time_duration {
+                        self.time = 0.0;
+                    }
+                    self.playing = playing;
+                    ui.toggle_value(&mut self.params.time_loop, "🔁")
+                        .on_hover_text("Loop");
+                    ui.toggle_value(&mut self.params.pen_reveal, "✍")
+                        .on_hover_text(
+                            "Pen Drawing: draw the paths along their length over the timeline",
+                        );
+                    ui.add(
+                        egui::Slider::new(&mut self.time, 0.0..=self.params.time_duration)
+                            .text("t")
+                            .fixed_decimals(2),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.params.time_speed)
+                            .range(0.1..=10.0)
+                            .speed(0.05)
+                            .prefix("×"),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut self.params.time_duration)
+                            .range(0.1..=600.0)
+                            .speed(0.1)
+                            .suffix(" s"),
+                    );
+                },
+            );
             ui.add(toggle("LCD Coordinates", &mut self.params.lcd_coords));
             ui.add(toggle(
                 "Show Intermediate Dash",
                 &mut self.params.show_inter_dash,
             ));
             ui.add(toggle("Colorful Blocks", &mut self.params.colorful_block));
+            ui.add(toggle("Show Canvas", &mut self.params.show_canvas));
+            ui.add(toggle("Check Canvas", &mut self.params.check_canvas));
+            if self.params.show_canvas || self.params.check_canvas {
+                for (v, name) in self.params.canvas.iter_mut().zip(["x", "y", "w", "h"]) {
+                    ui.add(egui::DragValue::new(v).prefix(format!("{name}: ")));
+                }
+            }
+            ui.add(toggle("Fixed Point", &mut self.params.fixed_point));
+            if self.params.fixed_point {
+                let bits = &mut self.params.fixed_point_bits;
+                egui::ComboBox::from_id_salt("fixed_point_format")
+                    .selected_text(format!("Q{}.{}", bits[0], bits[1]))
+                    .show_ui(ui, |ui| {
+                        for (preset, name) in
+                            [([32, 0], "Integer"), ([8, 8], "Q8.8"), ([16, 16], "Q16.16")]
+                        {
+                            ui.selectable_value(bits, preset, name);
+                        }
+                    });
+                ui.add(
+                    egui::DragValue::new(&mut bits[0])
+                        .range(1..=32)
+                        .prefix("int: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut bits[1])
+                        .range(0..=31)
+                        .prefix("frac: "),
+                );
+                ui.label(format!("Max deviation: {:.6}", self.cache.max_deviation));
+            }
+            ui.horizontal(|ui| {
+                ui.label("Draw:");
+                let before = self.pen.mode;
+                for (mode, name) in [
+                    (PenMode::Off, "Off"),
+                    (PenMode::Pen, "✒ Pen"),
+                    (PenMode::Freehand, "✏ Freehand"),
+                ] {
+                    ui.selectable_value(&mut self.pen.mode, mode, name);
+                }
+                if self.pen.mode != before {
+                    self.finish_pen_path(false);
+                    self.pen.stroke.clear();
+                    self.pen.error = None;
+                }
+                match self.pen.mode {
+                    PenMode::Off => {}
+                    PenMode::Pen => {
+                        if ui.button("Finish Path").clicked() {
+                            self.finish_pen_path(false);
+                        }
+                        if ui.button("Close Path").clicked() {
+                            self.finish_pen_path(true);
+                        }
+                    }
+                    PenMode::Freehand => {
+                        ui.add(
+                            egui::DragValue::new(&mut self.pen.tolerance)
+                                .range(0.001..=f64::MAX)
+                                .speed(0.05)
+                                .prefix("tolerance: "),
+                        );
+                    }
+                }
+                if let Some(e) = &self.pen.error {
+                    ui.colored_label(egui::Color32::RED, e);
+                }
+            });
+            ui.add(toggle("Curve Analysis", &mut self.params.curve_analysis));
+            if self.params.curve_analysis {
+                ui.add(
+                    egui::Slider::new(&mut self.params.analysis_t, 0.0..=1.0)
+                        .text("t")
+                        .show_value(true),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.params.comb_scale)
+                        .range(0.0..=f64::INFINITY)
+                        .speed(0.05)
+                        .prefix("comb scale: "),
+                );
+            }
+        });
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ui_export_menu(&mut self, ui: &mut egui::Ui) {
+        let params = &mut self.export;
+        let options = &mut params.options;
+        egui::Grid::new("export_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Format");
+                egui::ComboBox::from_id_salt("export_format")
+                    .selected_text(options.format.name())
+                    .show_ui(ui, |ui| {
+                        for format in ExportFormat::ALL {
+                            if ui
+                                .selectable_value(&mut options.format, format, format.name())
+                                .changed()
+                            {
+                                params.path = std::path::Path::new(&params.path)
+                                    .with_extension(format.extension())
+                                    .display()
+                                    .to_string();
+                            }
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Sweep");
+                egui::ComboBox::from_id_salt("export_sweep")
+                    .selected_text(options.sweep.name())
+                    .show_ui(ui, |ui| {
+                        for sweep in Sweep::ALL {
+                            ui.selectable_value(&mut options.sweep, sweep, sweep.name());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Size");
+                ui.horizontal(|ui| {
+                    let [w, h] = &mut options.size;
+                    ui.add(egui::DragValue::new(w).range(1..=4096).prefix("w: "));
+                    ui.add(egui::DragValue::new(h).range(1..=4096).prefix("h: "));
+                });
+                ui.end_row();
+
+                ui.label("Frames");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut options.frames).range(1..=10000));
+                    ui.add(
+                        egui::DragValue::new(&mut options.fps)
+                            .range(1.0..=120.0)
+                            .suffix(" fps"),
+                    );
+                    if ui.button("Match Timeline").clicked() {
+                        options.frames =
+                            ((self.params.time_duration * options.fps).round() as usize).max(1);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Colors");
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_srgba_unmultiplied(&mut options.background);
+                    ui.label("background");
+                    ui.color_edit_button_srgba_unmultiplied(&mut options.color);
+                    ui.label("stroke");
+                });
+                ui.end_row();
+
+                ui.label("Fit");
+                ui.checkbox(&mut options.fit, "Fit the drawing to the frames");
+                ui.end_row();
+
+                ui.label("Path");
+                ui.text_edit_singleline(&mut params.path);
+                ui.end_row();
+            });
+
+        if let Some(job) = &self.export.job {
+            ui.horizontal(|ui| {
+                let done = job.progress.load(Ordering::Relaxed);
+                ui.add(
+                    egui::ProgressBar::new(done as f32 / job.frames as f32)
+                        .text(format!("frame {done} / {}", job.frames)),
+                );
+                if ui.button("Cancel").clicked() {
+                    job.cancel.store(true, Ordering::Relaxed);
+                }
+            });
+        } else if ui.button("Export").clicked() {
+            self.start_export();
+        }
+        if !self.export.status.is_empty() {
+            ui.label(&self.export.status);
+        }
+    }
+
+    /// Render the frames of the code, or of the morph when morphing over the time,
+    /// and write them out on another thread
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_export(&mut self) {
+        let mut options = self.export.options.clone();
+        options.lcd_coords = self.params.lcd_coords;
+        options.morph = (self.params.morph && options.sweep == Sweep::Time).then(|| MorphExport {
+            target: self.morph_target.clone(),
+            easing: Easing::ALL[self.params.morph_easing],
+            duration: self.params.time_duration,
+        });
+        let code = self.code.cast_ref::<String>().clone();
+        let path = self.export.path.clone();
+        let progress = Arc::new(AtomicUsize::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let frames = options.frames;
+
+        let handle = std::thread::spawn({
+            let (progress, cancel) = (progress.clone(), cancel.clone());
+            move || {
+                export_frames(&code, &options, &path, |done| {
+                    progress.store(done, Ordering::Relaxed);
+                    !cancel.load(Ordering::Relaxed)
+                })
+            }
+        });
+        self.export.status.clear();
+        self.export.job = Some(ExportJob {
+            handle,
+            progress,
+            cancel,
+            frames,
         });
     }
 
+    /// Keep repainting while the export runs, and tell what it did once it ends
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll_export(&mut self, ctx: &egui::Context) {
+        let Some(job) = &self.export.job else {
+            return;
+        };
+        if !job.handle.is_finished() {
+            ctx.request_repaint_after_secs(0.1);
+            return;
+        }
+        let Some(job) = self.export.job.take() else {
+            return;
+        };
+        let at_line = |e: ParseError| format!("Line {}: {}", e.cursor.row + 1, e.msg);
+        self.export.status = match job.handle.join() {
+            Ok(Ok(count)) => format!("Wrote {count} files"),
+            Ok(Err(ExportError::Code(e))) => at_line(e),
+            Ok(Err(ExportError::Target(e))) => format!("Morph target, {}", at_line(e)),
+            Ok(Err(ExportError::Write(e))) => e,
+            Ok(Err(ExportError::Cancelled)) => "Cancelled, the files are incomplete".to_owned(),
+            Err(_) => "The export crashed".to_owned(),
+        };
+    }
+
     fn ui_transform_panel(&mut self, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
             ui.heading("Transform Matrix");
@@ -546,6 +1166,229 @@ impl MainApp {
                 let t = self.create_transfer_url(&transfer_data);
                 ui.output_mut(|o| o.copied_text = format!("https://w-mai.github.io/vegravis/{t}"));
             }
+
+            if ui.button("🧹 Format").clicked() {
+                match format_code(self.code.cast_ref::<String>(), DEFAULT_FORMAT_PRECISION) {
+                    Ok(code) => self.code = AnyData::new(code),
+                    Err(e) => self.error = Some(e),
+                }
+            }
+
+            ui.menu_button("🔁 Convert", |ui| {
+                let mut to_cubic = None;
+                if ui.button("Conics to Quads").clicked() {
+                    to_cubic = Some(false);
+                }
+                if ui.button("Conics to Cubics").clicked() {
+                    to_cubic = Some(true);
+                }
+                if let Some(to_cubic) = to_cubic {
+                    match convert_conics(self.code.cast_ref::<String>(), to_cubic) {
+                        Ok(code) => self.code = AnyData::new(code),
+                        Err(e) => self.error = Some(e),
+                    }
+                    ui.close_menu();
+                }
+
+                ui.separator();
+                if ui.button("Quads to Cubics").clicked() {
+                    match elevate_quads(self.code.cast_ref::<String>()) {
+                        Ok(code) => self.code = AnyData::new(code),
+                        Err(e) => self.error = Some(e),
+                    }
+                    ui.close_menu();
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Cubics to Quads").clicked() {
+                        match cubics_to_quads(
+                            self.code.cast_ref::<String>(),
+                            self.params.quad_tolerance,
+                        ) {
+                            Ok(code) => self.code = AnyData::new(code),
+                            Err(e) => self.error = Some(e),
+                        }
+                        ui.close_menu();
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut self.params.quad_tolerance)
+                            .range(0.001..=f64::INFINITY)
+                            .speed(0.01)
+                            .prefix("tolerance: "),
+                    );
+                });
+                ui.checkbox(&mut self.params.show_quad_error, "Show Quad Error");
+                if let Some(approximation) = &self.cache.quad_approximation {
+                    ui.label(format!(
+                        "{} quads, max error {:.6}",
+                        approximation.count, approximation.max_error
+                    ));
+                }
+
+                ui.separator();
+                if ui.button("Bake Transforms").clicked() {
+                    match bake_transforms(self.code.cast_ref::<String>()) {
+                        Ok(code) => self.code = AnyData::new(code),
+                        Err(e) => self.error = Some(e),
+                    }
+                    ui.close_menu();
+                }
+                if ui.button("Copy Baked Code").clicked() {
+                    match bake_transforms(self.code.cast_ref::<String>()) {
+                        Ok(code) => ui.output_mut(|o| o.copied_text = code),
+                        Err(e) => self.error = Some(e),
+                    }
+                    ui.close_menu();
+                }
+            });
+
+            ui.menu_button("📐 Refactor", |ui| {
+                let params = &mut self.refactor;
+                let mut transform = None;
+
+                ui.horizontal(|ui| {
+                    if ui.button("Fit to Box").clicked() {
+                        transform = Some(CodeTransform::FitToBox(params.fit_box));
+                    }
+                    for (v, prefix) in params.fit_box.iter_mut().zip(["x: ", "y: ", "w: ", "h: "]) {
+                        ui.add(egui::DragValue::new(v).prefix(prefix));
+                    }
+                });
+                if ui.button("Translate to Origin").clicked() {
+                    transform = Some(CodeTransform::TranslateToOrigin);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Scale by").clicked() {
+                        transform = Some(CodeTransform::Scale(params.scale));
+                    }
+                    ui.add(egui::DragValue::new(&mut params.scale).speed(0.01));
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Round to").clicked() {
+                        transform = Some(CodeTransform::Round(params.decimals));
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut params.decimals)
+                            .range(0..=DEFAULT_FORMAT_PRECISION)
+                            .suffix(" decimals"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Quantize to Grid").clicked() {
+                        transform = Some(CodeTransform::Quantize(params.grid));
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut params.grid)
+                            .range(0.001..=f64::MAX)
+                            .speed(0.1),
+                    );
+                });
+
+                ui.separator();
+                let mut simplify = None;
+                ui.horizontal(|ui| {
+                    if ui.button("Simplify Lines").clicked() {
+                        simplify = Some(false);
+                    }
+                    if ui.button("Fit Curves").clicked() {
+                        simplify = Some(true);
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut params.simplify_tolerance)
+                            .range(0.001..=f64::MAX)
+                            .speed(0.01)
+                            .prefix("tolerance: "),
+                    );
+                });
+
+                if let Some(transform) = transform {
+                    match transform_code(self.code.cast_ref::<String>(), &transform) {
+                        Ok(code) => self.code = AnyData::new(code),
+                        Err(e) => self.error = Some(e),
+                    }
+                    ui.close_menu();
+                }
+                if let Some(fit) = simplify {
+                    let code = self.code.cast_ref::<String>();
+                    let res = if fit {
+                        fit_curves(code, params.simplify_tolerance)
+                    } else {
+                        simplify_lines(code, params.simplify_tolerance)
+                    };
+                    match res {
+                        Ok(code) => self.code = AnyData::new(code),
+                        Err(e) => self.error = Some(e),
+                    }
+                    ui.close_menu();
+                }
+            });
+
+            #[cfg(not(target_arch = "wasm32"))]
+            ui.menu_button("🎬 Export", |ui| {
+                self.ui_export_menu(ui);
+            });
+
+            ui.menu_button("⊕ Boolean", |ui| {
+                // the cached generator is of the code once it parses
+                if !self.code.equal::<String, String>(&self.cache.code) {
+                    ui.label("Fix the code first");
+                    return;
+                }
+                if self.params.morph {
+                    ui.label("Turn off the morph first");
+                    return;
+                }
+                let shapes = self.cache.generator.shapes();
+                if shapes.len() < 2 {
+                    ui.label("Draw at least two closed shapes");
+                    return;
+                }
+
+                let params = &mut self.refactor;
+                let shape_name = |i: usize| {
+                    shapes.get(i).map_or("-".to_owned(), |shape| {
+                        format!("#{} at line {}", i + 1, shape.spans[0].0.row + 1)
+                    })
+                };
+                for (i, (v, label)) in params.boolean_shapes.iter_mut().zip(["A", "B"]).enumerate()
+                {
+                    egui::ComboBox::from_id_salt(("boolean_shape", i))
+                        .selected_text(shape_name(*v))
+                        .show_ui(ui, |ui| {
+                            for j in 0..shapes.len() {
+                                ui.selectable_value(v, j, shape_name(j));
+                            }
+                        })
+                        .response
+                        .on_hover_text(label);
+                }
+                egui::ComboBox::from_id_salt("boolean_op")
+                    .selected_text(params.boolean_op.name())
+                    .show_ui(ui, |ui| {
+                        for op in BooleanOp::ALL {
+                            ui.selectable_value(&mut params.boolean_op, op, op.name());
+                        }
+                    });
+
+                let [a, b] = params.boolean_shapes;
+                let op = params.boolean_op;
+                ui.horizontal(|ui| {
+                    let apply = ui.button("Apply").clicked();
+                    let copy = ui.button("Copy Result").clicked();
+                    let code = self.code.cast_ref::<String>();
+                    let res = if apply {
+                        boolean_code(code, a, b, op).map(|code| self.code = AnyData::new(code))
+                    } else if copy {
+                        boolean_path(&self.cache.generator, a, b, op)
+                            .map(|path| ui.output_mut(|o| o.copied_text = path))
+                    } else {
+                        return;
+                    };
+                    if let Err(e) = res {
+                        self.error = Some(e);
+                    }
+                    ui.close_menu();
+                });
+            });
         });
 
         ui.separator();
@@ -556,46 +1399,198 @@ impl MainApp {
     fn ui_visualizer(&mut self, ui: &mut egui::Ui) {
         if self.selected_sample.is_empty() && self.hovered_sample.is_empty() {
             let mut has_error = false;
-            if !self.code.equal::<String, String>(&self.cache.code)
-                || self.params != self.cache.params
-            {
-                let mut generator = VecLineGen::default();
-                let mut parser = CodeParser::new(self.code.clone::<String>(), &mut generator);
-                // 通过parser产生generator需要的前置数据
-                has_error = match parser.parse() {
-                    Ok(vlg) => {
-                        let ops_count = vlg.len() as i64;
-                        self.params.vis_progress_max = ops_count;
-                        if !self.code.equal::<String, String>(&self.cache.code) {
-                            self.params.vis_progress = ops_count;
+            let retime = self.time != self.cache.time;
+            let reparse = !self.code.equal::<String, String>(&self.cache.code)
+                || !self.params.parses_as(&self.cache.params)
+                || (self.params.morph && self.morph_target != self.cache.morph_target)
+                || (retime && self.params.morph);
+            // the panels only analyze while open, opening them analyzes what is drawn
+            let measure_open = self.panel_status.contains(MEASURE_PANEL);
+            let intersections_open = self.panel_status.contains(INTERSECTIONS_PANEL);
+            let hit_test_open = self.panel_status.contains(HIT_TEST_PANEL);
+            let unanalyzed = (measure_open && self.cache.measures.is_none())
+                || (intersections_open && self.cache.intersections.is_none())
+                || (hit_test_open && self.hit_point.is_some() && self.cache.hit_test.is_none());
+            let redraw = reparse
+                || !self.params.draws_as(&self.cache.params)
+                || (retime && self.cache.generator.is_timed())
+                || unanalyzed;
+            if redraw {
+                if reparse {
+                    let mut generator = VecLineGen::default();
+                    // 通过parser产生generator需要的前置数据
+                    let parsed = self.drawn_code().and_then(|code| {
+                        let mut parser = CodeParser::new(AnyData::new(code), &mut generator);
+                        parser.parse().map(|_| ()).map_err(MorphError::Source)
+                    });
+                    self.morph_error = None;
+                    has_error = match parsed {
+                        Ok(()) => {
+                            let ops_count = generator.len() as i64;
+                            self.params.vis_progress_max = ops_count;
+                            if !self.code.equal::<String, String>(&self.cache.code) {
+                                self.params.vis_progress = ops_count;
+                            }
+
+                            // the lints of the morphed code would point into another text
+                            self.diagnostics = if self.params.morph {
+                                vec![]
+                            } else {
+                                generator.diagnose(
+                                    self.params.check_canvas.then_some(self.params.canvas),
+                                )
+                            };
+
+                            self.cache.generator = generator;
+                            self.cache.code = self.code.clone::<String>();
+                            self.cache.params = self.params.clone();
+                            self.cache.morph_target = self.morph_target.clone();
+                            false
                         }
+                        Err(MorphError::Source(e)) => {
+                            error!("Error: {:?}", e);
+                            self.error = Some(e);
+                            self.diagnostics.clear();
+                            true
+                        }
+                        Err(MorphError::Target(e)) => {
+                            error!("Morph target error: {:?}", e);
+                            self.error = None;
+                            self.morph_error = Some(e);
+                            self.diagnostics.clear();
+                            true
+                        }
+                    };
+                }
+                // the untimed ops are replayed from the generator cache, the analyses go over
+                // all the ops again
+                self.cache.time = self.time;
+                let mut generator = std::mem::take(&mut self.cache.generator);
+                generator.set_time(self.time);
+                if !has_error {
+                    self.cache.lines = generator.generate(0..self.params.vis_progress);
+                }
 
-                        let parsed = vlg.generate(0..self.params.vis_progress);
+                self.device_texture = None;
+                ui.ctx().request_repaint();
 
-                        self.cache.lines = parsed.clone();
-                        self.cache.code = self.code.clone::<String>();
-                        self.cache.params = self.params.clone();
-                        false
+                self.cache.measures = measure_open.then(|| {
+                    if has_error {
+                        vec![]
+                    } else {
+                        generator.measure(0..self.params.vis_progress)
                     }
-                    Err(e) => {
-                        error!("Error: {:?}", e);
-                        self.error = Some(e);
-                        true
+                });
+                self.cache.intersections = intersections_open.then(|| {
+                    if has_error {
+                        vec![]
+                    } else {
+                        generator.intersections(0..self.params.vis_progress)
                     }
+                });
+                self.cache.quantized_lines.clear();
+                self.cache.max_deviation = 0.0;
+                if !has_error && self.params.fixed_point {
+                    let [int_bits, frac_bits] = self.params.fixed_point_bits;
+                    generator.set_fixed_point(Some(FixedPoint {
+                        int_bits,
+                        frac_bits,
+                    }));
+                    let quantized = generator.generate(0..self.params.vis_progress);
+                    generator.set_fixed_point(None);
+                    self.cache.max_deviation = self
+                        .cache
+                        .lines
+                        .iter()
+                        .flatten()
+                        .zip(quantized.iter().flatten())
+                        .map(|(a, b)| {
+                            let [ax, ay] = a.pos();
+                            let [bx, by] = b.pos();
+                            (ax.cast_ref::<f64>() - bx.cast_ref::<f64>())
+                                .hypot(ay.cast_ref::<f64>() - by.cast_ref::<f64>())
+                        })
+                        .fold(0.0, f64::max);
+                    self.cache.quantized_lines = quantized;
                 }
+                self.cache.generator = generator;
+                self.update_hit_test();
+            }
+            if redraw || !self.params.analyzes_as(&self.cache.params) {
+                self.cache.quad_approximation =
+                    (!has_error && self.params.show_quad_error).then(|| {
+                        self.cache.generator.quad_approximation(
+                            0..self.params.vis_progress,
+                            self.params.quad_tolerance,
+                        )
+                    });
+                self.cache.analysis = (!has_error && self.params.curve_analysis).then(|| {
+                    self.cache.generator.analyze_curves(
+                        0..self.params.vis_progress,
+                        self.params.analysis_t,
+                        self.params.comb_scale,
+                    )
+                });
+            }
+            // the device preview is rasterized for its size, the other params only change the view
+            if (self.params.device_size, self.params.lcd_coords)
+                != (self.cache.params.device_size, self.cache.params.lcd_coords)
+            {
+                self.device_texture = None;
+            }
+            if !reparse {
+                self.cache.params = self.params.clone();
             }
             if !has_error {
                 self.error = None;
             }
-            CommonVecVisualizer::new(self.params.trans_matrix).plot(
+            let visualizer = CommonVecVisualizer::new(self.params.trans_matrix)
+                .with_overlay(self.cache.quantized_lines.clone())
+                .with_canvas(self.params.show_canvas.then_some(self.params.canvas))
+                .with_analysis(self.cache.analysis.clone())
+                .with_approximation(self.cache.quad_approximation.clone())
+                .with_intersections(
+                    self.cache
+                        .intersections
+                        .iter()
+                        .flatten()
+                        .filter(|_| intersections_open)
+                        .map(|x| x.point)
+                        .collect(),
+                )
+                .with_hit(
+                    self.cache
+                        .hit_test
+                        .as_ref()
+                        .filter(|_| hit_test_open)
+                        .map(|h| (h.point, h.nearest.as_ref().map(|n| n.point))),
+                )
+                .with_guides(self.pen_guides());
+            let (lines, reveal_marker) = if self.params.pen_reveal {
+                let reveal = reveal_lines(&self.cache.lines, self.time / self.params.time_duration);
+                (reveal.lines, reveal.reveal_marker)
+            } else {
+                (self.cache.lines.clone(), None)
+            };
+            let visualizer = visualizer.with_reveal_marker(reveal_marker);
+            let drawing = self.pen.mode != PenMode::Off;
+            visualizer.plot(
                 ui,
-                self.cache.lines.clone(),
+                lines,
                 has_error,
                 self.params.show_inter_dash,
                 self.params.colorful_block,
                 self.params.lcd_coords,
-                |x| x,
+                // dragging draws instead of moving the plot
+                |plot| plot.allow_drag(!drawing),
             );
+            let input = visualizer.input();
+            if drawing {
+                self.pen_input(input);
+            } else if hit_test_open && let Some(point) = input.clicked {
+                self.hit_point = Some(point);
+                self.update_hit_test();
+            }
         } else {
             let visualizer =
                 CommonVecVisualizer::new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
@@ -610,6 +1605,468 @@ impl MainApp {
         }
     }
 
+    /// Append the commands to the code, each on its own line
+    fn append_code(&mut self, commands: &[String]) {
+        self.code = AnyData::new(append_commands(self.code.cast_ref::<String>(), commands));
+    }
+
+    /// The matrix taking drawn points to the params of the code appended,
+    /// None after telling the pen why it can't draw
+    fn pen_to_params(&mut self) -> Option<[[f64; 3]; 3]> {
+        let to_params = self.cache.generator.drawn_to_params();
+        self.pen.error = to_params.is_none().then(|| {
+            "The transforms at the end of the code are singular, nothing drawn".to_owned()
+        });
+        to_params
+    }
+
+    fn pen_input(&mut self, input: PlotInput) {
+        // only a click or the end of a drag adds commands
+        let to_params = if input.clicked.is_some() || input.drag_stopped.is_some() {
+            let Some(to_params) = self.pen_to_params() else {
+                self.pen.drag_start = None;
+                self.pen.stroke.clear();
+                return;
+            };
+            to_params
+        } else {
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        };
+
+        let pen = &mut self.pen;
+        pen.pointer = input.pointer;
+        let point = |p: [f64; 2]| {
+            let p = pen_point(p, to_params);
+            format!("{}, {}", fmt_num(p[0]), fmt_num(p[1]))
+        };
+        let mut commands = vec![];
+
+        match pen.mode {
+            PenMode::Off => {}
+            PenMode::Pen => {
+                if input.drag_started.is_some() {
+                    pen.drag_start = input.drag_started;
+                }
+                // an anchor, and the handle going out of it if dragged
+                let anchor = match (pen.drag_start, input.drag_stopped, input.clicked) {
+                    (Some(p), Some(q), _) => Some((p, Some(q))),
+                    (_, _, Some(p)) => Some((p, None)),
+                    _ => None,
+                };
+                if input.drag_stopped.is_some() {
+                    pen.drag_start = None;
+                }
+                if let Some((p, handle_out)) = anchor {
+                    match pen.anchors {
+                        None => {
+                            commands.push(format!("move, {}", point(p)));
+                            pen.anchors = Some([p, p]);
+                        }
+                        Some([first, last]) => {
+                            // the handle coming in mirrors the one going out
+                            let handle_in =
+                                handle_out.map(|q| [2.0 * p[0] - q[0], 2.0 * p[1] - q[1]]);
+                            commands.push(match (pen.handle, handle_in) {
+                                (None, None) => format!("line, {}", point(p)),
+                                (h1, h2) => format!(
+                                    "cubi, {}, {}, {}",
+                                    point(h1.unwrap_or(last)),
+                                    point(h2.unwrap_or(p)),
+                                    point(p)
+                                ),
+                            });
+                            pen.anchors = Some([first, p]);
+                        }
+                    }
+                    pen.handle = handle_out;
+                }
+            }
+            PenMode::Freehand => {
+                if let Some(p) = input.drag_started {
+                    pen.stroke = vec![p];
+                }
+                if !pen.stroke.is_empty()
+                    && let Some(p) = input.pointer
+                    && pen.stroke.last() != Some(&p)
+                {
+                    pen.stroke.push(p);
+                }
+                if input.drag_stopped.is_some() && pen.stroke.len() > 1 {
+                    let stroke = std::mem::take(&mut pen.stroke);
+                    let stroke = stroke
+                        .into_iter()
+                        .map(|p| pen_point(p, to_params))
+                        .collect::<Vec<_>>();
+                    commands.push(format!(
+                        "move, {}, {}",
+                        fmt_num(stroke[0][0]),
+                        fmt_num(stroke[0][1])
+                    ));
+                    commands.extend(fit_points(&stroke, pen.tolerance));
+                } else if input.drag_stopped.is_some() {
+                    pen.stroke.clear();
+                }
+            }
+        }
+
+        if !commands.is_empty() {
+            self.append_code(&commands);
+        }
+    }
+
+    /// Finish the path being drawn with the pen, closing it back to the first anchor if asked
+    fn finish_pen_path(&mut self, close: bool) {
+        let pen = &mut self.pen;
+        let (anchors, handle) = (pen.anchors.take(), pen.handle.take());
+        let Some([first, last]) = anchors.filter(|_| close) else {
+            return;
+        };
+        let Some(to_params) = self.pen_to_params() else {
+            return;
+        };
+        let point = |p: [f64; 2]| {
+            let p = pen_point(p, to_params);
+            format!("{}, {}", fmt_num(p[0]), fmt_num(p[1]))
+        };
+        let close = match handle {
+            Some(h) => format!("cubi, {}, {}, {}", point(h), point(first), point(first)),
+            None if first != last => format!("line, {}", point(first)),
+            None => return self.append_code(&["end".to_owned()]),
+        };
+        self.append_code(&[close, "end".to_owned()]);
+    }
+
+    /// Lines showing what the pen is about to draw
+    fn pen_guides(&self) -> Vec<Vec<[f64; 2]>> {
+        let pen = &self.pen;
+        match pen.mode {
+            PenMode::Off => vec![],
+            PenMode::Pen => {
+                let mut guides = vec![];
+                if let (Some(p), Some(q)) = (pen.drag_start, pen.pointer) {
+                    guides.push(vec![[2.0 * p[0] - q[0], 2.0 * p[1] - q[1]], p, q]);
+                }
+                if let (Some([_, last]), Some(h)) = (pen.anchors, pen.handle) {
+                    guides.push(vec![last, h]);
+                }
+                if let (Some([_, last]), Some(q)) = (pen.anchors, pen.drag_start.or(pen.pointer)) {
+                    guides.push(vec![last, q]);
+                }
+                guides
+            }
+            PenMode::Freehand => vec![pen.stroke.clone()],
+        }
+    }
+
+    /// Test the clicked point against the paths drawn so far
+    fn update_hit_test(&mut self) {
+        // the cached generator is already at the drawn code and time
+        let open = self.panel_status.contains(HIT_TEST_PANEL);
+        self.cache.hit_test = self.hit_point.filter(|_| open).map(|point| {
+            self.cache
+                .generator
+                .hit_test(0..self.params.vis_progress, point)
+        });
+    }
+
+    fn ui_hit_test_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(hit) = &self.cache.hit_test else {
+            ui.label("Click the plot to test a point");
+            return;
+        };
+        let num = |v: f64| format!("{v:.3}");
+        let inside = |v: bool| if v { "Inside" } else { "Outside" };
+
+        egui::Grid::new("hit_test_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Point");
+                ui.label(format!("({}, {})", num(hit.point[0]), num(hit.point[1])));
+                ui.end_row();
+
+                ui.label("Winding Number");
+                ui.label(hit.winding.to_string());
+                ui.end_row();
+
+                ui.label("Nonzero");
+                ui.label(inside(hit.nonzero()));
+                ui.end_row();
+
+                ui.label("Even-Odd");
+                ui.label(inside(hit.even_odd()));
+                ui.end_row();
+
+                if let Some(n) = &hit.nearest {
+                    ui.label("Nearest Point");
+                    ui.label(format!("({}, {})", num(n.point[0]), num(n.point[1])));
+                    ui.end_row();
+
+                    ui.label("Distance");
+                    ui.label(num(n.distance));
+                    ui.end_row();
+
+                    ui.label("Segment");
+                    ui.label(format!("#{} at line {}", n.op + 1, n.cursor.row + 1));
+                    ui.end_row();
+                }
+            });
+
+        if hit.paths.is_empty() {
+            return;
+        }
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .show(ui, |ui| {
+                egui::Grid::new("hit_test_paths_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Subpath");
+                        ui.strong("Winding");
+                        ui.strong("Nonzero");
+                        ui.strong("Even-Odd");
+                        ui.end_row();
+
+                        for (i, path) in hit.paths.iter().enumerate() {
+                            ui.label(format!("#{} at line {}", i + 1, path.start.row + 1));
+                            ui.label(path.winding.to_string());
+                            ui.label(inside(path.nonzero()));
+                            ui.label(inside(path.even_odd()));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    /// The code to draw, moved toward the morph target when morphing
+    fn drawn_code(&self) -> Result<String, MorphError> {
+        let code = self.code.cast_ref::<String>();
+        if !self.params.morph {
+            return Ok(code.clone());
+        }
+        let t = self.time / self.params.time_duration;
+        let easing = Easing::ALL[self.params.morph_easing];
+        morph_code(code, &self.morph_target, easing.ease(t))
+    }
+
+    fn ui_morph_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(toggle("Morph", &mut self.params.morph));
+            egui::ComboBox::from_id_salt("morph_easing")
+                .selected_text(Easing::ALL[self.params.morph_easing].name())
+                .show_ui(ui, |ui| {
+                    for (i, easing) in Easing::ALL.iter().enumerate() {
+                        ui.selectable_value(&mut self.params.morph_easing, i, easing.name());
+                    }
+                });
+        });
+        ui.label("The timeline moves every number of the code toward the target below, from t = 0 to its end");
+        ui.horizontal(|ui| {
+            if ui.button("Use Current Code").clicked() {
+                self.morph_target = self.code.cast_ref::<String>().clone();
+            }
+            if ui.button("Swap").clicked() {
+                let code = self.code.cast_ref::<String>().clone();
+                self.code = AnyData::new(std::mem::replace(&mut self.morph_target, code));
+            }
+        });
+        if self.params.morph
+            && let Some(e) = &self.error
+        {
+            ui.colored_label(egui::Color32::RED, &e.msg);
+        }
+        if let Some(e) = &self.morph_error {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!("Target line {}: {}", e.cursor.row + 1, e.msg),
+            );
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(320.0)
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.morph_target)
+                        .code_editor()
+                        .desired_width(f32::INFINITY)
+                        .hint_text("Target program with the same commands"),
+                );
+            });
+    }
+
+    fn ui_intersections_panel(&mut self, ui: &mut egui::Ui) {
+        let intersections = self.cache.intersections.as_deref().unwrap_or_default();
+        let self_crossings = intersections.iter().filter(|x| x.same_subpath).count();
+        ui.label(format!(
+            "{} crossings, {} within one subpath",
+            intersections.len(),
+            self_crossings
+        ));
+        if intersections.is_empty() {
+            return;
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .max_height(240.0)
+            .show(ui, |ui| {
+                egui::Grid::new("intersections_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Point");
+                        ui.strong("Command");
+                        ui.strong("Command");
+                        ui.strong("Subpath");
+                        ui.end_row();
+
+                        for x in intersections {
+                            let [px, py] = x.point;
+                            ui.label(format!("({px:.3}, {py:.3})"));
+                            for (op, cursor) in x.ops.iter().zip(&x.cursors) {
+                                ui.label(format!("#{} at line {}", op + 1, cursor.row + 1));
+                            }
+                            ui.label(if x.same_subpath { "Same" } else { "Across" });
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn ui_measure_panel(&mut self, ui: &mut egui::Ui) {
+        let measures = self.cache.measures.as_deref().unwrap_or_default();
+        if self.measure_subpath.is_some_and(|i| i >= measures.len()) {
+            self.measure_subpath = None;
+        }
+
+        let subpath_name = |i: Option<usize>| match i {
+            Some(i) => format!("Subpath #{} at line {}", i + 1, measures[i].start.row + 1),
+            None => "Whole program".to_owned(),
+        };
+        egui::ComboBox::from_id_salt("measure_subpath")
+            .selected_text(subpath_name(self.measure_subpath))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.measure_subpath, None, subpath_name(None));
+                for i in 0..measures.len() {
+                    ui.selectable_value(&mut self.measure_subpath, Some(i), subpath_name(Some(i)));
+                }
+            });
+        ui.separator();
+
+        let m = match self.measure_subpath {
+            Some(i) => measures[i].clone(),
+            None => Measure::merge(measures),
+        };
+        let num = |v: f64| format!("{v:.3}");
+        // counterclockwise with y up turns clockwise on screen
+        let winding = match (m.area > 1e-9, m.area < -1e-9) {
+            (true, _) if self.params.lcd_coords => "Clockwise",
+            (true, _) => "Counterclockwise",
+            (_, true) if self.params.lcd_coords => "Counterclockwise",
+            (_, true) => "Clockwise",
+            _ => "-",
+        };
+
+        egui::Grid::new("measure_grid")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Length");
+                ui.label(num(m.length));
+                ui.end_row();
+
+                ui.label("Signed Area");
+                ui.label(num(m.area));
+                ui.end_row();
+
+                ui.label("Winding");
+                ui.label(winding);
+                ui.end_row();
+
+                ui.label("Bounding Box");
+                ui.label(m.bbox.map_or("-".to_owned(), |[x0, y0, x1, y1]| {
+                    format!(
+                        "({}, {}) - ({}, {})\n{} x {}",
+                        num(x0),
+                        num(y0),
+                        num(x1),
+                        num(y1),
+                        num(x1 - x0),
+                        num(y1 - y0)
+                    )
+                }));
+                ui.end_row();
+
+                ui.label("Centroid");
+                ui.label(
+                    m.centroid
+                        .map_or("-".to_owned(), |[x, y]| format!("({}, {})", num(x), num(y))),
+                );
+                ui.end_row();
+
+                ui.label("Points");
+                ui.label(m.points.to_string());
+                ui.end_row();
+            });
+    }
+
+    fn ui_device_preview(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Device Preview");
+        ui.horizontal_wrapped(|ui| {
+            let [w, h] = &mut self.params.device_size;
+            ui.add(egui::DragValue::new(w).range(1..=2048).prefix("w: "));
+            ui.add(egui::DragValue::new(h).range(1..=2048).prefix("h: "));
+            ui.add(
+                egui::DragValue::new(&mut self.params.device_zoom)
+                    .range(1..=16)
+                    .prefix("zoom: ")
+                    .suffix("x"),
+            );
+        });
+        ui.separator();
+
+        // show what the fixed point backend gets if it is on
+        let lines = if self.cache.quantized_lines.is_empty() {
+            &self.cache.lines
+        } else {
+            &self.cache.quantized_lines
+        };
+        let texture = self.device_texture.get_or_insert_with(|| {
+            ui.ctx().load_texture(
+                "device_preview",
+                rasterize(lines, self.params.device_size, self.params.lcd_coords),
+                egui::TextureOptions::NEAREST,
+            )
+        });
+
+        let zoom = self.params.device_zoom as f32;
+        egui::ScrollArea::both().show(ui, |ui| {
+            let (rect, _) = ui.allocate_exact_size(texture.size_vec2() * zoom, Sense::hover());
+            let painter = ui.painter_at(rect);
+            painter.image(
+                texture.id(),
+                rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                egui::Color32::WHITE,
+            );
+
+            // pixel boundaries are only visible if pixels are large enough
+            if zoom >= 4.0 {
+                let stroke = egui::Stroke::new(1.0, egui::Color32::from_gray(40));
+                let [w, h] = texture.size();
+                for x in 0..=w {
+                    painter.vline(rect.left() + x as f32 * zoom, rect.y_range(), stroke);
+                }
+                for y in 0..=h {
+                    painter.hline(rect.x_range(), rect.top() + y as f32 * zoom, stroke);
+                }
+            }
+        });
+    }
+
     fn ui_about(&mut self, ui: &mut egui::Ui) {
         const VERSION: &str = env!("CARGO_PKG_VERSION");
         use egui::special_emojis::GITHUB;
@@ -624,8 +2081,7 @@ impl MainApp {
 
             ui.separator();
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.ctx().screen_rect().width() > 600.0
-                    || self.panel_status.contains(WINDOW_NAMES[5][1])
+                if ui.ctx().screen_rect().width() > 600.0 || self.panel_status.contains(ABOUT_PANEL)
                 {
                     ui.horizontal_wrapped(|ui| {
                         ui.label(format!("Version: {VERSION}"));
@@ -636,7 +2092,7 @@ impl MainApp {
                         );
                     });
                 } else if ui.add(egui::Button::new("ℹ")).clicked() {
-                    self.panel_status.insert(WINDOW_NAMES[5][1].to_owned());
+                    self.panel_status.insert(ABOUT_PANEL.to_owned());
                 }
             });
         });
@@ -647,10 +2103,10 @@ impl MainApp {
     fn create_transfer_url(&self, transfer_data: &TransferData) -> String {
         let config = bincode::config::standard();
         if let Ok(data) = bincode::encode_to_vec(transfer_data, config) {
-            let mut t = BASE64_URL_SAFE_NO_PAD.encode(data);
-
-            t.insert(0, '?');
-            return t;
+            return format!(
+                "?{TRANSFER_VERSION}.{}",
+                BASE64_URL_SAFE_NO_PAD.encode(data)
+            );
         };
         Default::default()
     }
@@ -666,14 +2122,28 @@ impl MainApp {
             return;
         }
 
-        if let Ok(data) = BASE64_URL_SAFE_NO_PAD.decode(query) {
+        // links from before the versioning have no '.', which base64 never contains
+        let (version, data) = match query.split_once('.') {
+            Some((version, data)) => (version.parse::<u32>().ok(), data),
+            None => (None, query.as_str()),
+        };
+        if let Ok(data) = BASE64_URL_SAFE_NO_PAD.decode(data) {
             let config = bincode::config::standard();
-            if let Ok((t, _s)) =
-                bincode::decode_from_slice(&data, config) as Result<(TransferData, _), _>
+            if version == Some(TRANSFER_VERSION)
+                && let Ok((t, _s)) =
+                    bincode::decode_from_slice(&data, config) as Result<(TransferData, _), _>
             {
                 self.code = AnyData::new(t.code);
                 self.params = t.params.unwrap_or_default();
 
+                return;
+            }
+            // the params of other versions don't fit, their code still does
+            if let Ok((code, _s)) =
+                bincode::decode_from_slice(&data, config) as Result<(String, _), _>
+            {
+                self.code = AnyData::new(code);
+
                 return;
             }
         }
@@ -0,0 +1,256 @@
+use crate::common_vec_op::{
+    DEFAULT_FORMAT_PRECISION, Easing, ExportError, ExportFormat, ExportOptions, MorphExport, Sweep,
+    export_frames, format_code,
+};
+use crate::interfaces::ParseError;
+use std::io::Read;
+
+const USAGE: &str = "Usage:
+    vegravis                                         Start the visualizer
+    vegravis fmt [--precision N] [--write] [FILE]    Format the code in FILE or stdin
+    vegravis export [OPTIONS] -o OUTPUT [FILE]       Render the frames of the code in FILE or stdin
+
+Export options:
+    --format gif|apng|png    Animated GIF, APNG or numbered PNG files, by the OUTPUT extension by default
+    --sweep time|progress|pen
+                             Step the time t, the ops drawn or the length drawn by a pen
+                             from frame to frame, time by default
+    --size WxH               Pixels of every frame, 320x240 by default
+    --fps N                  Frames per second, 30 by default
+    --frames N               Count of frames, 60 by default
+    --background RRGGBB[AA]  Background color, 000000 by default
+    --color RRGGBB[AA]       Stroke color, ffffff by default
+    --lcd                    Origin at the top left corner with y down
+    --no-fit                 One unit per pixel instead of fitting the drawing to the frames
+    --morph TARGET           Morph the code toward the code in TARGET over the time sweep
+    --easing linear|ease-in|ease-out|ease-in-out
+                             Easing of the morph, linear by default
+    --duration SECONDS       Time the morph takes, all the frames by default";
+
+/// Run the subcommand in `args`, which has no program name in it.
+/// Returns the exit code, or None if there is no subcommand and the GUI should be started,
+/// args launchers pass like file paths or platform flags are left to the GUI.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let (cmd, rest) = args.split_first()?;
+    let res = match cmd.as_str() {
+        "fmt" => cmd_fmt(rest),
+        "export" => cmd_export(rest),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => return None,
+    };
+
+    match res {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{e}");
+            Some(1)
+        }
+    }
+}
+
+fn read_code(file: Option<&String>) -> Result<String, String> {
+    match file {
+        Some(file) => std::fs::read_to_string(file).map_err(|e| format!("{file}: {e}")),
+        None => {
+            let mut code = String::new();
+            std::io::stdin()
+                .read_to_string(&mut code)
+                .map_err(|e| format!("stdin: {e}"))?;
+            Ok(code)
+        }
+    }
+}
+
+fn parse_color(s: &str) -> Result<[u8; 4], String> {
+    let invalid = || format!("Invalid color '{s}', expected RRGGBB or RRGGBBAA");
+    if !matches!(s.len(), 6 | 8) {
+        return Err(invalid());
+    }
+    let mut color = [255; 4];
+    for (i, c) in color.iter_mut().enumerate().take(s.len() / 2) {
+        *c = s
+            .get(2 * i..2 * i + 2)
+            .and_then(|h| u8::from_str_radix(h, 16).ok())
+            .ok_or_else(invalid)?;
+    }
+    Ok(color)
+}
+
+fn cmd_export(args: &[String]) -> Result<(), String> {
+    let mut options = ExportOptions::default();
+    let mut format = None;
+    let mut output = None;
+    let mut file = None;
+    let mut morph = None;
+    let mut easing = Easing::Linear;
+    let mut duration = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{name} needs a value\n{USAGE}"))
+        };
+        match arg.as_str() {
+            "--format" => {
+                format = Some(match value("--format")?.as_str() {
+                    "gif" => ExportFormat::Gif,
+                    "apng" => ExportFormat::Apng,
+                    "png" => ExportFormat::PngSequence,
+                    f => return Err(format!("Unknown format '{f}'")),
+                });
+            }
+            "--sweep" => {
+                options.sweep = match value("--sweep")?.as_str() {
+                    "time" => Sweep::Time,
+                    "progress" => Sweep::Progress,
+                    "pen" => Sweep::Pen,
+                    s => return Err(format!("Unknown sweep '{s}'")),
+                };
+            }
+            "--size" => {
+                let size = value("--size")?;
+                options.size = size
+                    .split_once('x')
+                    .and_then(|(w, h)| Some([w.parse().ok()?, h.parse().ok()?]))
+                    .filter(|[w, h]| *w > 0 && *h > 0)
+                    .ok_or_else(|| format!("Invalid size '{size}', expected WxH"))?;
+            }
+            "--fps" => {
+                options.fps = value("--fps")?
+                    .parse()
+                    .ok()
+                    .filter(|fps| *fps > 0.0)
+                    .ok_or_else(|| "--fps needs a positive number".to_owned())?;
+            }
+            "--frames" => {
+                options.frames = value("--frames")?
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| "--frames needs a positive number".to_owned())?;
+            }
+            "--background" => options.background = parse_color(value("--background")?)?,
+            "--color" => options.color = parse_color(value("--color")?)?,
+            "--lcd" => options.lcd_coords = true,
+            "--no-fit" => options.fit = false,
+            "--morph" => morph = Some(value("--morph")?),
+            "--easing" => {
+                easing = match value("--easing")?.as_str() {
+                    "linear" => Easing::Linear,
+                    "ease-in" => Easing::EaseIn,
+                    "ease-out" => Easing::EaseOut,
+                    "ease-in-out" => Easing::EaseInOut,
+                    e => return Err(format!("Unknown easing '{e}'")),
+                };
+            }
+            "--duration" => {
+                duration = Some(
+                    value("--duration")?
+                        .parse()
+                        .ok()
+                        .filter(|d| *d > 0.0)
+                        .ok_or_else(|| "--duration needs a positive number".to_owned())?,
+                );
+            }
+            "--output" | "-o" => output = Some(value("--output")?),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{arg}'\n{USAGE}")),
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("Unexpected argument '{arg}'\n{USAGE}")),
+        }
+    }
+    let output = output.ok_or_else(|| format!("export needs an OUTPUT\n{USAGE}"))?;
+    options.format = format.unwrap_or(if output.ends_with(".gif") {
+        ExportFormat::Gif
+    } else {
+        ExportFormat::Apng
+    });
+
+    if let Some(target) = morph {
+        if options.sweep != Sweep::Time {
+            return Err("--morph only works with the time sweep".to_owned());
+        }
+        options.morph = Some(MorphExport {
+            target: std::fs::read_to_string(target).map_err(|e| format!("{target}: {e}"))?,
+            easing,
+            duration: duration.unwrap_or(options.frames as f64 / options.fps),
+        });
+    }
+
+    let code = read_code(file)?;
+    let at = |file: &str, e: ParseError| {
+        format!(
+            "{file}:{}:{}: Error: {}",
+            e.cursor.row + 1,
+            e.cursor.col,
+            e.msg
+        )
+    };
+    export_frames(&code, &options, output, |_| true).map_err(|e| match e {
+        ExportError::Code(e) => at(file.map_or("stdin", |f| f.as_str()), e),
+        ExportError::Target(e) => at(morph.map_or("", |f| f.as_str()), e),
+        ExportError::Write(e) => e,
+        ExportError::Cancelled => "Cancelled".to_owned(),
+    })?;
+    Ok(())
+}
+
+fn cmd_fmt(args: &[String]) -> Result<(), String> {
+    let mut precision = DEFAULT_FORMAT_PRECISION;
+    let mut write = false;
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--precision" => {
+                precision = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| "--precision needs a number".to_owned())?;
+            }
+            "--write" | "-w" => write = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{arg}'\n{USAGE}")),
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(format!("Unexpected argument '{arg}'\n{USAGE}")),
+        }
+    }
+    if write && file.is_none() {
+        return Err("--write needs a FILE".to_owned());
+    }
+
+    let code = read_code(file)?;
+    let formatted = format_code(&code, precision).map_err(|e| {
+        format!(
+            "{}:{}:{}: Error: {}",
+            file.map_or("stdin", |f| f.as_str()),
+            e.cursor.row + 1,
+            e.cursor.col,
+            e.msg
+        )
+    })?;
+
+    match file {
+        Some(file) if write => std::fs::write(file, formatted).map_err(|e| format!("{file}: {e}")),
+        _ => {
+            print!("{formatted}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_args_start_the_gui() {
+        for arg in ["-psn_0_12345", "drawing.txt"] {
+            assert_eq!(run_cli(&[arg.to_owned()]), None);
+        }
+        assert_eq!(run_cli(&["fmt".to_owned(), "--wrte".to_owned()]), Some(1));
+    }
+}
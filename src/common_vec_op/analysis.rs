@@ -0,0 +1,216 @@
+/// Stds
+use std::ops::Range;
+
+/// Self
+use super::VecLineGen;
+use super::curve::Curve;
+use super::ops::op_cubi::{cubic_quad_count, cubic_to_quads};
+
+/// Teeth of the curvature comb on every curve
+const COMB_TEETH: usize = 40;
+
+/// Longest comb tooth against the size of the drawing, when the comb scale is 1
+const COMB_LENGTH: f64 = 0.15;
+
+/// Length of the tangent and normal arrows against the size of the drawing
+const ARROW_LENGTH: f64 = 0.08;
+
+/// Samples on every quad approximating a cubic
+const ERROR_SAMPLES: usize = 16;
+
+/// Marks for reviewing the quality of the QUAD and CUBI curves, in drawn coordinates
+#[derive(Debug, Clone, Default)]
+pub struct CurveAnalysis {
+    /// from the curve to the comb outline, pointing away from the center of curvature
+    pub teeth: Vec<[[f64; 2]; 2]>,
+    /// tips of the teeth of every curve
+    pub combs: Vec<Vec<[f64; 2]>>,
+    /// where x or y turns back
+    pub extrema: Vec<[f64; 2]>,
+    pub inflections: Vec<[f64; 2]>,
+    pub cusps: Vec<[f64; 2]>,
+    /// unit tangent and left normal at the chosen t, scaled to the drawing
+    pub tangents: Vec<[[f64; 2]; 2]>,
+    pub normals: Vec<[[f64; 2]; 2]>,
+}
+
+/// Quads approximating the CUBI curves, in drawn coordinates
+#[derive(Debug, Clone, Default)]
+pub struct QuadApproximation {
+    /// flattened quads
+    pub quads: Vec<Vec<[f64; 2]>>,
+    /// from the cubic to the quad at the same t, magnified to be seen
+    pub errors: Vec<[[f64; 2]; 2]>,
+    pub max_error: f64,
+    /// quads needed in all
+    pub count: usize,
+}
+
+impl VecLineGen {
+    /// Analyze every QUAD and CUBI drawn by the ops in `range`,
+    /// tangents and normals are taken at `t`, teeth are scaled by `comb_scale`
+    pub fn analyze_curves(&self, range: Range<i64>, t: f64, comb_scale: f64) -> CurveAnalysis {
+        let ctx = self.run(range);
+        let world_matrix = ctx.state().current_world_trans();
+        let curves = ctx
+            .curves()
+            .iter()
+            .filter(|c| matches!(c, Curve::Quad(_) | Curve::Cubic(_)))
+            .map(|c| c.transform(world_matrix))
+            .collect::<Vec<_>>();
+
+        let mut res = CurveAnalysis::default();
+        let Some(size) = drawing_size(&curves) else {
+            return res;
+        };
+
+        let samples = curves
+            .iter()
+            .map(|c| {
+                (0..=COMB_TEETH)
+                    .map(|i| {
+                        let t = i as f64 / COMB_TEETH as f64;
+                        (c.point(t), c.derivative(t), c.curvature(t))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // a cusp has no curvature to speak of, so keep the big ones near it from flattening the rest
+        let mut kappas = samples
+            .iter()
+            .flatten()
+            .filter_map(|(_, _, k)| k.map(f64::abs))
+            .collect::<Vec<_>>();
+        kappas.sort_by(f64::total_cmp);
+        let reference = kappas
+            .get(kappas.len() * 9 / 10)
+            .copied()
+            .unwrap_or_default();
+        let max_length = COMB_LENGTH * size * comb_scale;
+        let scale = if reference > 0.0 {
+            max_length / reference
+        } else {
+            0.0
+        };
+
+        for (curve, samples) in curves.iter().zip(samples) {
+            let mut comb = vec![];
+            for (p, d, k) in samples {
+                let Some(k) = k else {
+                    continue;
+                };
+                let n = left_normal(d);
+                let len = (-k * scale).clamp(-3.0 * max_length, 3.0 * max_length);
+                let tip = [p[0] + n[0] * len, p[1] + n[1] * len];
+                res.teeth.push([p, tip]);
+                comb.push(tip);
+            }
+            res.combs.push(comb);
+
+            let at = |ts: Vec<f64>| ts.into_iter().map(|t| curve.point(t)).collect::<Vec<_>>();
+            res.extrema.extend(at(curve.extrema()));
+            res.inflections.extend(at(curve.inflections()));
+            res.cusps.extend(at(curve.cusps()));
+
+            let (p, d) = (curve.point(t), curve.derivative(t));
+            if d[0].hypot(d[1]) > 0.0 {
+                let len = ARROW_LENGTH * size;
+                let (u, n) = (unit(d), left_normal(d));
+                res.tangents
+                    .push([p, [p[0] + u[0] * len, p[1] + u[1] * len]]);
+                res.normals
+                    .push([p, [p[0] + n[0] * len, p[1] + n[1] * len]]);
+            }
+        }
+
+        res
+    }
+}
+
+impl VecLineGen {
+    /// Approximate every CUBI drawn by the ops in `range` with quads, as the conversion does
+    pub fn quad_approximation(&self, range: Range<i64>, tolerance: f64) -> QuadApproximation {
+        let ctx = self.run(range);
+        let world_matrix = ctx.state().current_world_trans();
+
+        let mut res = QuadApproximation::default();
+        // pairs of the point on the cubic and on the quad
+        let mut pairs = vec![];
+        for curve in ctx.curves() {
+            let Curve::Cubic(p) = curve.transform(world_matrix) else {
+                continue;
+            };
+            let cubic = Curve::Cubic(p);
+            let count = cubic_quad_count(p, tolerance);
+            res.count += count;
+
+            let mut start = p[0];
+            for (k, [c, e]) in cubic_to_quads(p, count).into_iter().enumerate() {
+                let quad = Curve::Quad([start, c, e]);
+                start = e;
+                res.quads.push(
+                    (0..=ERROR_SAMPLES)
+                        .map(|i| quad.point(i as f64 / ERROR_SAMPLES as f64))
+                        .collect(),
+                );
+                for i in 0..=ERROR_SAMPLES {
+                    let t = i as f64 / ERROR_SAMPLES as f64;
+                    let on_cubic = cubic.point((k as f64 + t) / count as f64);
+                    let on_quad = quad.point(t);
+                    res.max_error = res
+                        .max_error
+                        .max((on_quad[0] - on_cubic[0]).hypot(on_quad[1] - on_cubic[1]));
+                    pairs.push((on_cubic, on_quad));
+                }
+            }
+        }
+
+        let size = drawing_size(
+            &ctx.curves()
+                .iter()
+                .map(|c| c.transform(world_matrix))
+                .collect::<Vec<_>>(),
+        );
+        let magnify = match size {
+            Some(size) if res.max_error > 1e-12 => COMB_LENGTH * size / res.max_error,
+            _ => 0.0,
+        };
+        res.errors = pairs
+            .into_iter()
+            .map(|(a, b)| {
+                [
+                    a,
+                    [
+                        a[0] + (b[0] - a[0]) * magnify,
+                        a[1] + (b[1] - a[1]) * magnify,
+                    ],
+                ]
+            })
+            .collect();
+
+        res
+    }
+}
+
+fn unit(d: [f64; 2]) -> [f64; 2] {
+    let len = d[0].hypot(d[1]);
+    [d[0] / len, d[1] / len]
+}
+
+fn left_normal(d: [f64; 2]) -> [f64; 2] {
+    let u = unit(d);
+    [-u[1], u[0]]
+}
+
+/// Diagonal of the box around the control points
+fn drawing_size(curves: &[Curve]) -> Option<f64> {
+    let [x0, y0, x1, y1] = curves.iter().flat_map(|c| c.points()).fold(
+        None::<[f64; 4]>,
+        |bbox, [x, y]| match bbox {
+            None => Some([x, y, x, y]),
+            Some([x0, y0, x1, y1]) => Some([x0.min(x), y0.min(y), x1.max(x), y1.max(y)]),
+        },
+    )?;
+    let size = (x1 - x0).hypot(y1 - y0);
+    (size > 0.0).then_some(size)
+}
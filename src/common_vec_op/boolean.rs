@@ -0,0 +1,227 @@
+/// 3rds
+use i_overlay::core::fill_rule::FillRule;
+use i_overlay::core::overlay_rule::OverlayRule;
+use i_overlay::float::single::SingleFloatOverlay;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{Cursor, IVisData, ParseError, Span};
+
+/// Self
+use super::VecLineData;
+use super::VecLineGen;
+use super::convert::{fmt_num, parse_untimed, replace_spans};
+use super::ops::{GenerateCtx, calc_trans_stack, inverse_matrix};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BooleanOp {
+    Union,
+    Intersect,
+    Difference,
+    Xor,
+}
+
+impl BooleanOp {
+    pub const ALL: [BooleanOp; 4] = [
+        BooleanOp::Union,
+        BooleanOp::Intersect,
+        BooleanOp::Difference,
+        BooleanOp::Xor,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BooleanOp::Union => "Union",
+            BooleanOp::Intersect => "Intersect",
+            BooleanOp::Difference => "Difference",
+            BooleanOp::Xor => "XOR",
+        }
+    }
+
+    fn rule(&self) -> OverlayRule {
+        match self {
+            BooleanOp::Union => OverlayRule::Union,
+            BooleanOp::Intersect => OverlayRule::Intersect,
+            BooleanOp::Difference => OverlayRule::Difference,
+            BooleanOp::Xor => OverlayRule::Xor,
+        }
+    }
+}
+
+/// Closed area drawn by the code, a subpath or all subpaths of one op like TEXT
+#[derive(Debug, Clone)]
+pub struct Shape {
+    /// spans of the path ops drawing it
+    pub spans: Vec<Span>,
+    /// flattened subpaths, in drawn coordinates
+    pub contours: Vec<Vec<[f64; 2]>>,
+    /// local and world transform in effect where it starts
+    matrix: [[f64; 3]; 3],
+}
+
+fn is_path_op(name: &str) -> bool {
+    matches!(
+        name,
+        "MOVE" | "LINE" | "QUAD" | "CUBI" | "CONIC" | "END" | "TEXT"
+    )
+}
+
+impl VecLineGen {
+    /// Collect the shapes drawn by the ops, subpaths neither closed by END nor ending where
+    /// they start are left out
+    pub fn shapes(&self) -> Vec<Shape> {
+        let world_matrix = self
+            .run(0..self.ops().len() as i64)
+            .state()
+            .current_world_trans();
+
+        let mut shapes: Vec<Shape> = vec![];
+        let mut contour: Vec<[f64; 2]> = vec![];
+        let mut spans: Vec<Span> = vec![];
+        let mut matrix = world_matrix;
+
+        let mut finish = |contour: &mut Vec<[f64; 2]>, spans: &mut Vec<Span>, matrix, ended| {
+            if contour.len() > 2 && (ended || contour.first() == contour.last()) {
+                match shapes.last_mut() {
+                    // subpaths of one op like TEXT make up one shape
+                    Some(shape) if shape.spans == *spans => shape.contours.push(contour.clone()),
+                    _ => shapes.push(Shape {
+                        spans: spans.clone(),
+                        contours: vec![contour.clone()],
+                        matrix,
+                    }),
+                }
+            }
+            contour.clear();
+            spans.clear();
+        };
+
+        let mut gen_ctx = AnyData::new(GenerateCtx::default());
+        for op in self.ops() {
+            for op in op.expand() {
+                let name = op.dsc.name()[0];
+                if name == "MOVE" {
+                    finish(&mut contour, &mut spans, matrix, false);
+                }
+                if contour.is_empty() && name != "END" {
+                    let local_matrix = gen_ctx
+                        .cast_ref::<GenerateCtx>()
+                        .state()
+                        .current_local_trans();
+                    matrix = calc_trans_stack(&vec![world_matrix, local_matrix]);
+                }
+
+                let points = AnyData::convert_from_vec::<VecLineData>(op.operate(&mut gen_ctx));
+                if !is_path_op(name) {
+                    continue;
+                }
+                if spans.last() != Some(&op.span) {
+                    spans.push(op.span.clone());
+                }
+                for p in points {
+                    let p = p.matrix(world_matrix).cast::<VecLineData>();
+                    if contour.last() != Some(&[p.x(), p.y()]) {
+                        contour.push([p.x(), p.y()]);
+                    }
+                }
+
+                if name == "END" {
+                    finish(&mut contour, &mut spans, matrix, true);
+                }
+            }
+        }
+        finish(&mut contour, &mut spans, matrix, false);
+
+        shapes
+    }
+}
+
+/// Combine shape `a` and `b`, returns the code drawing the result in the coordinates of `a`
+fn combine(shapes: &[Shape], a: usize, b: usize, op: BooleanOp) -> Result<String, ParseError> {
+    let get = |i: usize| {
+        shapes.get(i).ok_or_else(|| ParseError {
+            msg: format!("No shape #{}", i + 1),
+            cursor: Cursor::default(),
+        })
+    };
+    let (shape_a, shape_b) = (get(a)?, get(b)?);
+    if a == b {
+        return Err(ParseError {
+            msg: "Boolean op needs two different shapes".to_owned(),
+            cursor: shape_a.spans[0].0.clone(),
+        });
+    }
+    let inv = inverse_matrix(shape_a.matrix).ok_or_else(|| ParseError {
+        msg: format!("Shape #{} is under a singular transform", a + 1),
+        cursor: shape_a.spans[0].0.clone(),
+    })?;
+
+    let result = shape_a
+        .contours
+        .overlay(&shape_b.contours, op.rule(), FillRule::EvenOdd);
+
+    Ok(result
+        .iter()
+        .flatten()
+        .map(|contour| {
+            contour
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    let p = VecLineData::new(p[0], p[1])
+                        .matrix(inv)
+                        .cast::<VecLineData>();
+                    let name = if i == 0 { "move" } else { "line" };
+                    format!("{name}, {}, {}", fmt_num(p.x()), fmt_num(p.y()))
+                })
+                .chain(["end".to_owned()])
+                .collect::<Vec<_>>()
+                .join(",\n")
+        })
+        .collect::<Vec<_>>()
+        .join(",\n"))
+}
+
+/// Code of the path combining shape `a` and `b` drawn by the generator, at the time it is set to
+pub fn boolean_path(
+    generator: &VecLineGen,
+    a: usize,
+    b: usize,
+    op: BooleanOp,
+) -> Result<String, ParseError> {
+    combine(&generator.shapes(), a, b, op).map(|text| text + ",\n")
+}
+
+/// Combine shape `a` and `b` of the code, the result is drawn where `a` is and `b` is removed
+pub fn boolean_code(code: &str, a: usize, b: usize, op: BooleanOp) -> Result<String, ParseError> {
+    let shapes = parse_untimed(code)?.shapes();
+    let text = combine(&shapes, a, b, op)?;
+
+    let (shape_a, shape_b) = (&shapes[a], &shapes[b]);
+    let mut edits = vec![(shape_a.spans[0].clone(), text)];
+    edits.extend(
+        shape_a.spans[1..]
+            .iter()
+            .chain(&shape_b.spans)
+            .map(|span| (span.clone(), String::new())),
+    );
+    Ok(replace_spans(code, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common_vec_op::convert::parse;
+
+    #[test]
+    fn shapes_are_closed_subpaths() {
+        let code = "move, 0, 0,\nline, 10, 0,\nline, 10, 10,\nend,\n\
+                    move, 20, 0,\nline, 30, 0,\nline, 30, 10,\n\
+                    move, 40, 0,\nline, 50, 0,\nline, 50, 10,\nline, 40, 0,\n";
+        let shapes = parse(code).unwrap().shapes();
+        let rows = shapes
+            .iter()
+            .map(|shape| shape.spans[0].0.row)
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [0, 7]);
+    }
+}
@@ -0,0 +1,509 @@
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{IParser, IVisData, ParseError, Span};
+
+/// Self
+use super::formatter::{DEFAULT_FORMAT_PRECISION, format_code};
+use super::generator::record_path;
+use super::ops::op_conic::{conic_quad_pow2, conic_to_cubics, conic_to_quads};
+use super::ops::op_cubi::{cubic_quad_count, cubic_to_quads};
+use super::ops::op_quad::quad_to_cubic;
+use super::ops::{GenerateCtx, calc_trans_stack, inverse_matrix, process_point};
+use super::{CodeParser, VecLineData, VecLineGen};
+
+/// Max distance between a conic and the quads/cubics it is converted into, once drawn
+const CONIC_TOLERANCE: f64 = 0.25;
+
+/// Format a number for code output, at most 3 decimals and no trailing zeros
+pub fn fmt_num(v: f64) -> String {
+    fmt_num_precision(v, 3)
+}
+
+/// Format a number for code output, at most `precision` decimals and no trailing zeros
+pub fn fmt_num_precision(v: f64, precision: usize) -> String {
+    let s = format!("{:.*}", precision, v);
+    let s = if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &s
+    };
+    if s == "-0" {
+        "0".to_owned()
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Replace each span of `code` with the text, spans must not overlap.
+/// An empty text removes the command in the span along with its indentation, trailing comma and line break
+pub fn replace_spans(code: &str, mut edits: Vec<(Span, String)>) -> String {
+    edits.sort_by_key(|(span, _)| span.0.pos);
+    debug_assert!(
+        edits.windows(2).all(|w| w[0].0.1.pos <= w[1].0.0.pos),
+        "replace_spans got overlapping spans"
+    );
+
+    let chars = code.chars().collect::<Vec<_>>();
+    let skip = |mut pos: usize, c: char| {
+        while pos < chars.len() && (chars[pos] == ' ' || chars[pos] == '\t') {
+            pos += 1;
+        }
+        if pos < chars.len() && chars[pos] == c {
+            pos += 1;
+        }
+        pos
+    };
+
+    // back over the indentation, if only that is before `pos` on its line
+    let indent_start = |pos: usize| {
+        let start = chars[..pos]
+            .iter()
+            .rposition(|c| *c != ' ' && *c != '\t')
+            .map_or(0, |i| i + 1);
+        if start == 0 || chars[start - 1] == '\n' {
+            start
+        } else {
+            pos
+        }
+    };
+
+    let mut res = String::new();
+    let mut pos = 0;
+    for ((l, r), text) in edits {
+        let l = if text.is_empty() {
+            indent_start(l.pos).max(pos)
+        } else {
+            l.pos
+        };
+        res.extend(&chars[pos.min(l)..l]);
+        res.push_str(&text);
+        pos = if text.is_empty() {
+            skip(skip(skip(r.pos, ','), '\r'), '\n')
+        } else {
+            r.pos
+        };
+    }
+    res.extend(&chars[pos.min(chars.len())..]);
+    res
+}
+
+/// Add the commands at the end of the code one per line, a comma is put after the last
+/// command first if it lacks one, before any comment following it
+pub fn append_commands(code: &str, commands: &[String]) -> String {
+    let chars = code.chars().collect::<Vec<_>>();
+    // the last char outside comments
+    let mut last = None;
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('/', Some('/')) => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            ('/', Some('*')) => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            ('"', _) => {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += if chars[i] == '\\' { 2 } else { 1 };
+                }
+                last = Some(i.min(chars.len() - 1));
+                i += 1;
+            }
+            (c, _) => {
+                if !c.is_whitespace() {
+                    last = Some(i);
+                }
+                i += 1;
+            }
+        }
+    }
+
+    let mut res = match last {
+        Some(l) if chars[l] != ',' => {
+            let mut res = chars[..=l].iter().collect::<String>();
+            res.push(',');
+            res.extend(&chars[l + 1..]);
+            res
+        }
+        _ => code.to_owned(),
+    };
+    if !res.is_empty() && !res.ends_with('\n') {
+        res.push('\n');
+    }
+    for command in commands {
+        res.push_str(command);
+        res.push_str(",\n");
+    }
+    res
+}
+
+pub(crate) fn parse(code: &str) -> Result<VecLineGen, ParseError> {
+    let mut generator = VecLineGen::default();
+    CodeParser::new(AnyData::new(code.to_owned()), &mut generator).parse()?;
+    Ok(generator)
+}
+
+/// Parse code to rewrite, params written with `t` are refused as the rewrite would freeze them
+pub(crate) fn parse_untimed(code: &str) -> Result<VecLineGen, ParseError> {
+    let generator = parse(code)?;
+    let timed = generator
+        .ops()
+        .iter()
+        .find_map(|op| op.timed_args.first().map(|arg| (op, arg)));
+    if let Some((op, arg)) = timed {
+        return Err(ParseError {
+            msg: format!("'{}' depends on the time, it can't be rewritten", arg.text),
+            cursor: op.arg_spans[arg.index].0.clone(),
+        });
+    }
+    Ok(generator)
+}
+
+/// Rewrite every `name` op in the code with the text `rewrite` returns for it, empty to leave it.
+/// It is given the cursor, the params and the matrix from the params to the drawn coordinates
+fn rewrite_ops(
+    code: &str,
+    name: &str,
+    rewrite: impl Fn([f64; 2], &[f64], [[f64; 3]; 3]) -> String,
+) -> Result<String, ParseError> {
+    let generator = parse_untimed(code)?;
+
+    let world_matrix = generator
+        .run(0..generator.ops().len() as i64)
+        .state()
+        .current_world_trans();
+
+    let mut gen_ctx = AnyData::new(GenerateCtx::default());
+    let mut edits = vec![];
+    for op in generator.ops() {
+        if op.dsc.name().contains(&name) {
+            let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+            // the cursor is already transformed, bring it back to where the params live
+            if let Some(inv) = inverse_matrix(ctx.state().current_local_trans()) {
+                let cursor = ctx.state().cursor();
+                let start = VecLineData::new(cursor.x, cursor.y)
+                    .matrix(inv)
+                    .cast::<VecLineData>();
+                let argv = op
+                    .argv
+                    .iter()
+                    .map(|v| *v.cast_ref::<f64>())
+                    .collect::<Vec<_>>();
+                let matrix =
+                    calc_trans_stack(&vec![world_matrix, ctx.state().current_local_trans()]);
+                let text = rewrite([start.x(), start.y()], &argv, matrix);
+                if !text.is_empty() {
+                    edits.push((op.span.clone(), text));
+                }
+            }
+        }
+        op.expand().iter().for_each(|op| {
+            op.operate(&mut gen_ctx);
+        });
+    }
+
+    Ok(replace_spans(code, edits))
+}
+
+fn quad_text([c, e]: [[f64; 2]; 2]) -> String {
+    format!(
+        "quad, {}, {}, {}, {}",
+        fmt_num(c[0]),
+        fmt_num(c[1]),
+        fmt_num(e[0]),
+        fmt_num(e[1])
+    )
+}
+
+pub(crate) fn cubic_text([c1, c2, e]: [[f64; 2]; 3]) -> String {
+    format!(
+        "cubi, {}, {}, {}, {}, {}, {}",
+        fmt_num(c1[0]),
+        fmt_num(c1[1]),
+        fmt_num(c2[0]),
+        fmt_num(c2[1]),
+        fmt_num(e[0]),
+        fmt_num(e[1])
+    )
+}
+
+/// Rewrite every `conic` in the code into `quad`s, or `cubi`s if `to_cubic` is set.
+/// The error is measured in drawn coordinates
+pub fn convert_conics(code: &str, to_cubic: bool) -> Result<String, ParseError> {
+    rewrite_ops(code, "CONIC", |start, argv, matrix| {
+        let p = [start, [argv[0], argv[1]], [argv[2], argv[3]]];
+        let drawn = p.map(|p| {
+            let p = VecLineData::new(p[0], p[1])
+                .matrix(matrix)
+                .cast::<VecLineData>();
+            [p.x(), p.y()]
+        });
+        let pow2 = conic_quad_pow2(drawn, argv[4], CONIC_TOLERANCE);
+        let text = if to_cubic {
+            conic_to_cubics(p, argv[4], pow2)
+                .into_iter()
+                .map(cubic_text)
+                .collect::<Vec<_>>()
+        } else {
+            conic_to_quads(p, argv[4], pow2)
+                .into_iter()
+                .map(quad_text)
+                .collect::<Vec<_>>()
+        };
+        text.join(",\n")
+    })
+}
+
+/// Rewrite every `quad` in the code into the `cubi` drawing the same curve
+pub fn elevate_quads(code: &str) -> Result<String, ParseError> {
+    rewrite_ops(code, "QUAD", |start, argv, _| {
+        cubic_text(quad_to_cubic([
+            start,
+            [argv[0], argv[1]],
+            [argv[2], argv[3]],
+        ]))
+    })
+}
+
+/// Rewrite every `cubi` in the code into the fewest `quad`s keeping within `tolerance` of it,
+/// the tolerance is in drawn coordinates
+pub fn cubics_to_quads(code: &str, tolerance: f64) -> Result<String, ParseError> {
+    rewrite_ops(code, "CUBI", |start, argv, matrix| {
+        let p = [
+            start,
+            [argv[0], argv[1]],
+            [argv[2], argv[3]],
+            [argv[4], argv[5]],
+        ];
+        let drawn = p.map(|p| {
+            let p = VecLineData::new(p[0], p[1])
+                .matrix(matrix)
+                .cast::<VecLineData>();
+            [p.x(), p.y()]
+        });
+        cubic_to_quads(p, cubic_quad_count(drawn, tolerance))
+            .into_iter()
+            .map(quad_text)
+            .collect::<Vec<_>>()
+            .join(",\n")
+    })
+}
+
+/// Evaluate the program and rewrite it as flat `move`/`line`/`quad`/`cubi` code,
+/// with all local and world transforms applied to the coordinates. Conics become quads
+/// and the outlines of OFFSET become lines, CLIP can't be baked
+pub fn bake_transforms(code: &str) -> Result<String, ParseError> {
+    let generator = parse_untimed(code)?;
+    let ops = generator
+        .ops()
+        .iter()
+        .flat_map(|op| op.expand())
+        .collect::<Vec<_>>();
+
+    // the world transform in the end is used for all points
+    let world_matrix = generator
+        .run(0..generator.ops().len() as i64)
+        .state()
+        .current_world_trans();
+
+    let fmt = |argv: &[f64]| {
+        argv.iter()
+            .map(|v| fmt_num_precision(*v, DEFAULT_FORMAT_PRECISION))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut gen_ctx = AnyData::new(GenerateCtx::default());
+    let mut lines = vec![];
+    for op in &ops {
+        let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+        // the cursor is already in local transformed space
+        let cursor = VecLineData::new(ctx.state().cursor().x, ctx.state().cursor().y)
+            .matrix(world_matrix)
+            .cast::<VecLineData>();
+        let start = [cursor.x(), cursor.y()];
+        let matrix = calc_trans_stack(&vec![world_matrix, ctx.state().current_local_trans()]);
+
+        let name = op.dsc.name()[0];
+        match name {
+            "MOVE" | "LINE" | "QUAD" | "CUBI" => {
+                let points = process_point(op.argv.clone(), matrix);
+                lines.push(format!("{}, {}", name.to_lowercase(), fmt(&points)));
+            }
+            "CONIC" => {
+                let p = process_point(op.argv.clone(), matrix);
+                let w = *op.argv[4].cast_ref::<f64>();
+                let p = [start, [p[0], p[1]], [p[2], p[3]]];
+                conic_to_quads(p, w, conic_quad_pow2(p, w, CONIC_TOLERANCE))
+                    .into_iter()
+                    .for_each(|[c, e]| {
+                        lines.push(format!("quad, {}", fmt(&[c[0], c[1], e[0], e[1]])))
+                    });
+            }
+            "END" => lines.push("end".to_owned()),
+            "CLIP" => {
+                return Err(ParseError {
+                    msg: "CLIP can't be baked, the parts it hides would be drawn".to_owned(),
+                    cursor: op.span.0.clone(),
+                });
+            }
+            _ => {}
+        }
+
+        let converted = AnyData::convert_from_vec::<VecLineData>(op.operate(&mut gen_ctx));
+        let ctx = gen_ctx.cast_mut::<GenerateCtx>();
+        record_path(ctx, &converted);
+
+        // lines drawn apart from the path, like the outlines of OFFSET
+        let extra_lines = std::mem::take(ctx.extra_lines_mut());
+        for line in &extra_lines {
+            for (i, p) in line.iter().enumerate() {
+                let p = p.matrix(world_matrix).cast::<VecLineData>();
+                let name = if i == 0 { "move" } else { "line" };
+                lines.push(format!("{name}, {}", fmt(&[p.x(), p.y()])));
+            }
+        }
+
+        // RESTORE may bring back an earlier cursor and the extra lines end away from it,
+        // so the path continues from there
+        let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+        let cursor = VecLineData::new(ctx.state().cursor().x, ctx.state().cursor().y)
+            .matrix(world_matrix)
+            .cast::<VecLineData>();
+        if name == "RESTORE" && [cursor.x(), cursor.y()] != start || !extra_lines.is_empty() {
+            lines.push(format!("move, {}", fmt(&[cursor.x(), cursor.y()])));
+        }
+    }
+
+    let baked = lines
+        .iter()
+        .map(|line| format!("{line},\n"))
+        .collect::<String>();
+    format_code(&baked, DEFAULT_FORMAT_PRECISION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::IVisDataGenerator;
+    use crate::sample_codes_list::SAMPLE_CODES_LIST;
+
+    fn spans(code: &str) -> Vec<Span> {
+        parse(code)
+            .unwrap()
+            .ops()
+            .iter()
+            .map(|op| op.span.clone())
+            .collect()
+    }
+
+    #[test]
+    fn replace_spans_removes_whole_lines() {
+        let code = "move, 0, 0,\n    line, 1, 1,\n    line, 2, 2,\n";
+        let spans = spans(code);
+        assert_eq!(
+            replace_spans(code, vec![(spans[1].clone(), String::new())]),
+            "move, 0, 0,\n    line, 2, 2,\n"
+        );
+        assert_eq!(
+            replace_spans(code, vec![(spans[2].clone(), String::new())]),
+            "move, 0, 0,\n    line, 1, 1,\n"
+        );
+        assert_eq!(
+            replace_spans(
+                code,
+                vec![
+                    (spans[2].clone(), String::new()),
+                    (spans[1].clone(), String::new())
+                ]
+            ),
+            "move, 0, 0,\n"
+        );
+    }
+
+    #[test]
+    fn replace_spans_keeps_the_rest_of_a_line() {
+        let code = "move, 0, 0, line, 1, 1,\nline, 2, 2,\n";
+        let spans = spans(code);
+        assert_eq!(
+            replace_spans(code, vec![(spans[1].clone(), String::new())]),
+            "move, 0, 0, line, 2, 2,\n"
+        );
+        assert_eq!(
+            replace_spans(code, vec![(spans[0].clone(), "move, 3, 3".to_owned())]),
+            "move, 3, 3, line, 1, 1,\nline, 2, 2,\n"
+        );
+    }
+
+    #[test]
+    fn append_commands_to_every_sample() {
+        let commands = ["move, 1, 2".to_owned(), "line, 3, 4".to_owned()];
+        for (name, code) in SAMPLE_CODES_LIST {
+            let len = parse(code).unwrap().ops().len();
+            let appended = append_commands(code, &commands);
+            let generator = parse(&appended).unwrap_or_else(|e| panic!("{name}: {}", e.msg));
+            assert_eq!(generator.ops().len(), len + 2, "{name}");
+        }
+    }
+
+    #[test]
+    fn append_commands_puts_the_comma_before_comments() {
+        let commands = ["line, 1, 1".to_owned()];
+        assert_eq!(
+            append_commands("move, 0, 0 // start\n", &commands),
+            "move, 0, 0, // start\nline, 1, 1,\n"
+        );
+        assert_eq!(
+            append_commands("text, 0, 0, 9, \"a, /* \\\" b\" /* c */", &commands),
+            "text, 0, 0, 9, \"a, /* \\\" b\", /* c */\nline, 1, 1,\n"
+        );
+        assert_eq!(append_commands("", &commands), "line, 1, 1,\n");
+    }
+
+    #[test]
+    fn timed_code_is_not_rewritten() {
+        let code = "move, 0, 0,\nline, 10 * t, 5,\n";
+        let e = bake_transforms(code).unwrap_err();
+        assert_eq!((e.cursor.row, e.cursor.col), (1, 6));
+        assert!(cubics_to_quads(code, 1.0).is_err());
+    }
+
+    #[test]
+    fn conic_tolerance_is_in_drawn_coordinates() {
+        let quads = |code: &str| convert_conics(code, false).unwrap().matches("quad").count();
+        let conic = "move, 0, 0,\nconic, 1, 1, 2, 0, 3,\n";
+        assert!(quads(&format!("scale, 100, 100,\n{conic}")) > quads(conic));
+    }
+
+    #[test]
+    fn bake_transforms_draws_offset_outlines_and_refuses_clip() {
+        let square = "move, 0, 0,\nline, 10, 0,\nline, 10, 10,\nline, 0, 10,\nline, 0, 0,\n";
+        let code = format!("scale, 2, 2,\n{square}offset, 1,\n");
+        let baked = bake_transforms(&code).unwrap();
+        let drawn = |code: &str| {
+            let generator = parse(code).unwrap();
+            generator
+                .generate(0..generator.ops().len() as i64)
+                .iter()
+                .flatten()
+                .map(|p| {
+                    let [x, y] = p.pos();
+                    [*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]
+                })
+                .fold(f64::NEG_INFINITY, |max, [x, y]| max.max(x).max(y))
+        };
+        // the outline goes past the square
+        assert!(drawn(&code) > 20.0);
+        assert!((drawn(&baked) - drawn(&code)).abs() < 1e-6, "{baked}");
+
+        let e = bake_transforms(&format!("clip, 0, 0, 5, 5,\n{square}")).unwrap_err();
+        assert_eq!(e.cursor.row, 0);
+    }
+}
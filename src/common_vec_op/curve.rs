@@ -0,0 +1,316 @@
+/// Crates
+use crate::interfaces::IVisData;
+
+/// Self
+use super::VecLineData;
+
+/// Abscissas and weights of the 5 point Gauss-Legendre rule on [-1, 1]
+const GAUSS_LEGENDRE: [(f64, f64); 5] = [
+    (0.0, 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683, 0.478_628_670_499_366_5),
+    (0.538_469_310_105_683, 0.478_628_670_499_366_5),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+];
+
+/// Pieces every segment is cut into for integration, the areas of beziers come out exact,
+/// lengths and anything on conics are approximated
+const INTEGRATION_STEPS: usize = 8;
+
+/// Analytic piece of a path, from the cursor to the end point, with its control points
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Curve {
+    Line([[f64; 2]; 2]),
+    Quad([[f64; 2]; 3]),
+    Cubic([[f64; 2]; 4]),
+    Conic([[f64; 2]; 3], f64),
+}
+
+fn lerp(a: [f64; 2], b: [f64; 2], t: f64) -> [f64; 2] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t]
+}
+
+fn diff(a: [f64; 2], b: [f64; 2], k: f64) -> [f64; 2] {
+    [(b[0] - a[0]) * k, (b[1] - a[1]) * k]
+}
+
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+/// Numerator and denominator of a conic, and their derivatives
+fn conic_parts(p: [[f64; 2]; 3], w: f64, t: f64) -> ([f64; 2], f64, [f64; 2], f64) {
+    let [b0, b1, b2] = [(1.0 - t) * (1.0 - t), 2.0 * w * t * (1.0 - t), t * t];
+    let [db0, db1, db2] = [-2.0 * (1.0 - t), 2.0 * w * (1.0 - 2.0 * t), 2.0 * t];
+    let n = [0, 1].map(|i| b0 * p[0][i] + b1 * p[1][i] + b2 * p[2][i]);
+    let dn = [0, 1].map(|i| db0 * p[0][i] + db1 * p[1][i] + db2 * p[2][i]);
+    (n, b0 + b1 + b2, dn, db0 + db1 + db2)
+}
+
+fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // a + b t + c t^2 = 0
+    if c.abs() < 1e-12 {
+        return if b.abs() < 1e-12 {
+            vec![]
+        } else {
+            vec![-a / b]
+        };
+    }
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return vec![];
+    }
+    let sqrt = disc.sqrt();
+    vec![(-b + sqrt) / (2.0 * c), (-b - sqrt) / (2.0 * c)]
+}
+
+/// Roots in (0, 1) of the polynomial of degree 2 at most sampled at t = 0, 0.5 and 1
+fn sampled_roots([g0, gh, g1]: [f64; 3]) -> Vec<f64> {
+    let a = g0;
+    let c = 2.0 * (g1 - 2.0 * gh + g0);
+    let b = g1 - g0 - c;
+    quadratic_roots(a, b, c)
+        .into_iter()
+        .filter(|t| 0.0 < *t && *t < 1.0)
+        .collect()
+}
+
+impl Curve {
+    pub fn points(&self) -> Vec<[f64; 2]> {
+        match self {
+            Curve::Line(p) => p.to_vec(),
+            Curve::Quad(p) | Curve::Conic(p, _) => p.to_vec(),
+            Curve::Cubic(p) => p.to_vec(),
+        }
+    }
+
+    /// The same curve under an affine matrix, conic weights stay as they are
+    pub fn transform(&self, matrix: [[f64; 3]; 3]) -> Curve {
+        let map = |p: [f64; 2]| {
+            let p = VecLineData::new(p[0], p[1])
+                .matrix(matrix)
+                .cast::<VecLineData>();
+            [p.x(), p.y()]
+        };
+        match *self {
+            Curve::Line(p) => Curve::Line(p.map(map)),
+            Curve::Quad(p) => Curve::Quad(p.map(map)),
+            Curve::Cubic(p) => Curve::Cubic(p.map(map)),
+            Curve::Conic(p, w) => Curve::Conic(p.map(map), w),
+        }
+    }
+
+    pub fn point(&self, t: f64) -> [f64; 2] {
+        match *self {
+            Curve::Line([p0, p1]) => lerp(p0, p1, t),
+            Curve::Quad([p0, p1, p2]) => lerp(lerp(p0, p1, t), lerp(p1, p2, t), t),
+            Curve::Cubic([p0, p1, p2, p3]) => {
+                let [a, b, c] = [lerp(p0, p1, t), lerp(p1, p2, t), lerp(p2, p3, t)];
+                lerp(lerp(a, b, t), lerp(b, c, t), t)
+            }
+            Curve::Conic(p, w) => {
+                let (n, d, _, _) = conic_parts(p, w, t);
+                [n[0] / d, n[1] / d]
+            }
+        }
+    }
+
+    /// Has the same sign as the derivative, and is a polynomial of degree 2 at most
+    fn derivative_numerator(&self, t: f64) -> [f64; 2] {
+        match *self {
+            Curve::Line([p0, p1]) => diff(p0, p1, 1.0),
+            Curve::Quad([p0, p1, p2]) => lerp(diff(p0, p1, 2.0), diff(p1, p2, 2.0), t),
+            Curve::Cubic([p0, p1, p2, p3]) => {
+                let [a, b, c] = [diff(p0, p1, 3.0), diff(p1, p2, 3.0), diff(p2, p3, 3.0)];
+                lerp(lerp(a, b, t), lerp(b, c, t), t)
+            }
+            Curve::Conic(p, w) => {
+                let (n, d, dn, dd) = conic_parts(p, w, t);
+                [dn[0] * d - n[0] * dd, dn[1] * d - n[1] * dd]
+            }
+        }
+    }
+
+    pub fn derivative(&self, t: f64) -> [f64; 2] {
+        let n = self.derivative_numerator(t);
+        match *self {
+            Curve::Conic(p, w) => {
+                let d = conic_parts(p, w, t).1;
+                [n[0] / (d * d), n[1] / (d * d)]
+            }
+            _ => n,
+        }
+    }
+
+    pub fn second_derivative(&self, t: f64) -> [f64; 2] {
+        match *self {
+            Curve::Line(_) => [0.0, 0.0],
+            Curve::Quad([p0, p1, p2]) => diff(diff(p0, p1, 2.0), diff(p1, p2, 2.0), 1.0),
+            Curve::Cubic([p0, p1, p2, p3]) => {
+                let [a, b, c] = [diff(p0, p1, 3.0), diff(p1, p2, 3.0), diff(p2, p3, 3.0)];
+                lerp(diff(a, b, 2.0), diff(b, c, 2.0), t)
+            }
+            Curve::Conic(..) => {
+                // no closed form worth the trouble here
+                let h = 1e-4;
+                let (a, b) = (self.derivative(t - h), self.derivative(t + h));
+                diff(a, b, 1.0 / (2.0 * h))
+            }
+        }
+    }
+
+    /// Signed curvature, positive when turning counterclockwise with y up, None where it stops
+    pub fn curvature(&self, t: f64) -> Option<f64> {
+        let d = self.derivative(t);
+        let speed = d[0].hypot(d[1]);
+        (speed > 1e-9 * self.size().max(1.0))
+            .then(|| cross(d, self.second_derivative(t)) / speed.powi(3))
+    }
+
+    /// Length of the control polygon, to tell what is small for the curve
+    pub fn size(&self) -> f64 {
+        self.points()
+            .windows(2)
+            .map(|p| (p[1][0] - p[0][0]).hypot(p[1][1] - p[0][1]))
+            .sum()
+    }
+
+    /// Parameters where the x or y of the curve turns back
+    pub fn extrema(&self) -> Vec<f64> {
+        let g = [0.0, 0.5, 1.0].map(|t| self.derivative_numerator(t));
+        (0..2)
+            .flat_map(|axis| sampled_roots(g.map(|g| g[axis])))
+            .collect()
+    }
+
+    /// Parameters where a cubic changes the side it turns to
+    pub fn inflections(&self) -> Vec<f64> {
+        let Curve::Cubic(_) = self else {
+            return vec![];
+        };
+        // the cubic terms of d x dd cancel out
+        let g = [0.0, 0.5, 1.0].map(|t| cross(self.derivative(t), self.second_derivative(t)));
+        let cusps = self.cusps();
+        sampled_roots(g)
+            .into_iter()
+            .filter(|t| cusps.iter().all(|c| (c - t).abs() > 1e-6))
+            .collect()
+    }
+
+    /// Parameters where the curve stops and turns sharply, the derivative is zero there
+    pub fn cusps(&self) -> Vec<f64> {
+        if let Curve::Line(_) = self {
+            return vec![];
+        }
+        let g = [0.0, 0.5, 1.0].map(|t| self.derivative_numerator(t));
+        let eps = 1e-6 * self.size().max(1e-9);
+        let candidates = match sampled_roots(g.map(|g| g[0])) {
+            // a vertical curve has x' = 0 everywhere
+            _ if g.iter().all(|g| g[0].abs() < eps) => sampled_roots(g.map(|g| g[1])),
+            roots => roots,
+        };
+        let mut cusps = candidates
+            .into_iter()
+            .filter(|t| {
+                let d = self.derivative_numerator(*t);
+                d[0].hypot(d[1]) < 1e-3 * self.size()
+            })
+            .collect::<Vec<_>>();
+        // a double root shows up twice
+        cusps.dedup_by(|a, b| (*a - *b).abs() < 1e-6);
+        cusps
+    }
+
+    /// Crossings of the ray going +x from `p`, counted +1 going up and -1 going down
+    pub fn winding(&self, p: [f64; 2]) -> i32 {
+        let mut cuts = vec![0.0];
+        let g = [0.0, 0.5, 1.0].map(|t| self.derivative_numerator(t)[1]);
+        let mut extrema = sampled_roots(g);
+        extrema.sort_by(f64::total_cmp);
+        cuts.extend(extrema);
+        cuts.push(1.0);
+
+        let y = |t: f64| self.point(t)[1] - p[1];
+        cuts.windows(2)
+            .map(|w| {
+                // y is monotonic in between, the lower end counts and the upper does not
+                let (mut a, mut b) = (w[0], w[1]);
+                let (ya, yb) = (y(a), y(b));
+                let dir = match (ya <= 0.0 && 0.0 < yb, yb <= 0.0 && 0.0 < ya) {
+                    (true, _) => 1,
+                    (_, true) => -1,
+                    _ => return 0,
+                };
+                for _ in 0..60 {
+                    let m = (a + b) / 2.0;
+                    if (y(m) > 0.0) == (dir > 0) {
+                        b = m;
+                    } else {
+                        a = m;
+                    }
+                }
+                if self.point((a + b) / 2.0)[0] > p[0] {
+                    dir
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    /// Parameter of the point on the curve nearest to `p`, and the distance to it
+    pub fn nearest(&self, p: [f64; 2]) -> (f64, f64) {
+        const SAMPLES: usize = 32;
+        let dist = |t: f64| {
+            let q = self.point(t);
+            (q[0] - p[0]).hypot(q[1] - p[1])
+        };
+        let samples = (0..=SAMPLES)
+            .map(|i| dist(i as f64 / SAMPLES as f64))
+            .collect::<Vec<_>>();
+        // golden section search around every sample closer than its neighbours,
+        // the closest sample may be on another loop of the curve than the closest point
+        let h = 1.0 / SAMPLES as f64;
+        let r = (5f64.sqrt() - 1.0) / 2.0;
+        (0..=SAMPLES)
+            .filter(|&i| {
+                (i == 0 || samples[i] <= samples[i - 1])
+                    && (i == SAMPLES || samples[i] <= samples[i + 1])
+            })
+            .map(|i| {
+                let best = i as f64 * h;
+                let (mut a, mut b) = ((best - h).max(0.0), (best + h).min(1.0));
+                for _ in 0..60 {
+                    let (c, d) = (b - r * (b - a), a + r * (b - a));
+                    if dist(c) < dist(d) {
+                        b = d;
+                    } else {
+                        a = c;
+                    }
+                }
+                let t = (a + b) / 2.0;
+                (t, dist(t))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or_default()
+    }
+
+    /// Integrate f(point, derivative) over t in [0, 1]
+    pub fn integrate(&self, f: impl Fn([f64; 2], [f64; 2]) -> f64) -> f64 {
+        let h = 1.0 / INTEGRATION_STEPS as f64;
+        (0..INTEGRATION_STEPS)
+            .map(|i| {
+                let mid = (i as f64 + 0.5) * h;
+                GAUSS_LEGENDRE
+                    .iter()
+                    .map(|(x, w)| {
+                        let t = mid + x * h / 2.0;
+                        w * f(self.point(t), self.derivative(t))
+                    })
+                    .sum::<f64>()
+                    * h
+                    / 2.0
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,386 @@
+/// Stds
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Crates
+use crate::interfaces::{IVisData, IVisDataGenerator, ParseError};
+
+/// Self
+use super::VecLineGen;
+use super::convert::parse;
+use super::morph::{Easing, MorphError, morph_code};
+use super::raster::rasterize_rgba;
+use super::reveal::reveal_lines;
+
+/// Lines of one frame, as the generator draws them
+type FrameLines = Vec<Vec<Box<dyn IVisData>>>;
+
+/// Part of the frames left blank around the drawing when fitting it
+const FIT_MARGIN: f64 = 0.05;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExportFormat {
+    Gif,
+    Apng,
+    /// numbered PNG files
+    PngSequence,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::Gif,
+        ExportFormat::Apng,
+        ExportFormat::PngSequence,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "GIF",
+            ExportFormat::Apng => "APNG",
+            ExportFormat::PngSequence => "PNG Sequence",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Gif => "gif",
+            ExportFormat::Apng | ExportFormat::PngSequence => "png",
+        }
+    }
+}
+
+/// What changes from frame to frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Sweep {
+    /// `t` goes up by one over the fps every frame
+    Time,
+    /// the ops drawn grow from none to all of them
+    Progress,
+    /// a pen draws the whole path at a steady pace along its length
+    Pen,
+}
+
+impl Sweep {
+    pub const ALL: [Sweep; 3] = [Sweep::Time, Sweep::Progress, Sweep::Pen];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sweep::Time => "Time",
+            Sweep::Progress => "Progress",
+            Sweep::Pen => "Pen",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub sweep: Sweep,
+    /// pixels of every frame
+    pub size: [u32; 2],
+    pub fps: f64,
+    pub frames: usize,
+    /// RGBA, a transparent one is kept in all formats
+    pub background: [u8; 4],
+    pub color: [u8; 4],
+    pub lcd_coords: bool,
+    /// scale and move the drawing to fill the frames, instead of one unit per pixel
+    pub fit: bool,
+    /// morph the code toward a target over the time, only with the time sweep
+    pub morph: Option<MorphExport>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Gif,
+            sweep: Sweep::Time,
+            size: [320, 240],
+            fps: 30.0,
+            frames: 60,
+            background: [0, 0, 0, 255],
+            color: [255, 255, 255, 255],
+            lcd_coords: false,
+            fit: true,
+            morph: None,
+        }
+    }
+}
+
+/// The code moving toward the target from the time 0 to the duration, it stays there after
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphExport {
+    pub target: String,
+    pub easing: Easing,
+    /// seconds
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExportError {
+    /// the code doesn't parse, or doesn't match the morph target
+    Code(ParseError),
+    /// the morph target doesn't parse
+    Target(ParseError),
+    /// the options are invalid, or encoding or writing a file failed
+    Write(String),
+    /// stopped before the last frame, the files may be partly written
+    Cancelled,
+}
+
+impl From<MorphError> for ExportError {
+    fn from(e: MorphError) -> Self {
+        match e {
+            MorphError::Source(e) => ExportError::Code(e),
+            MorphError::Target(e) => ExportError::Target(e),
+        }
+    }
+}
+
+/// Makes the lines of one frame at a time
+struct FrameSource<'a> {
+    code: &'a str,
+    options: &'a ExportOptions,
+    generator: VecLineGen,
+    /// everything drawn, for the pen to reveal
+    lines: FrameLines,
+}
+
+impl<'a> FrameSource<'a> {
+    fn new(code: &'a str, options: &'a ExportOptions) -> Result<Self, ExportError> {
+        let generator = parse(code).map_err(ExportError::Code)?;
+        let lines = match options.sweep {
+            Sweep::Pen => generator.generate(0..generator.len() as i64),
+            _ => vec![],
+        };
+        Ok(Self {
+            code,
+            options,
+            generator,
+            lines,
+        })
+    }
+
+    /// Lines of frame i, which is at the time i / fps
+    fn frame(&mut self, i: usize) -> Result<FrameLines, ExportError> {
+        let options = self.options;
+        let len = self.generator.len() as i64;
+        let last = options.frames.saturating_sub(1).max(1) as f64;
+        let t = i as f64 / options.fps;
+        Ok(match (options.sweep, &options.morph) {
+            (Sweep::Time, Some(morph)) => {
+                let progress = morph.easing.ease(t / morph.duration);
+                let code = morph_code(self.code, &morph.target, progress)?;
+                let mut generator = parse(&code).map_err(ExportError::Code)?;
+                generator.set_time(t);
+                generator.generate(0..len)
+            }
+            (Sweep::Time, None) => {
+                self.generator.set_time(t);
+                self.generator.generate(0..len)
+            }
+            (Sweep::Progress, _) => {
+                let end = (i as f64 / last * len as f64).round() as i64;
+                self.generator.generate(0..end)
+            }
+            (Sweep::Pen, _) => reveal_lines(&self.lines, i as f64 / last).lines,
+        })
+    }
+}
+
+/// Grow the box `[min, max]` around the points of the lines
+fn grow_bounds(bounds: &mut [[f64; 2]; 2], lines: &FrameLines) {
+    // a lone point is the cursor the drawing starts from, not part of it
+    for p in lines.iter().filter(|line| line.len() > 1).flatten() {
+        let [x, y] = p.pos();
+        for (i, v) in [*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]
+            .into_iter()
+            .enumerate()
+        {
+            bounds[0][i] = bounds[0][i].min(v);
+            bounds[1][i] = bounds[1][i].max(v);
+        }
+    }
+}
+
+/// Scale and move the box `[min, max]` into the frame size
+fn fit_map([min, max]: [[f64; 2]; 2], size: [u32; 2]) -> impl Fn([f64; 2]) -> [f64; 2] {
+    let [w, h] = [size[0] as f64, size[1] as f64];
+    let (bw, bh) = (max[0] - min[0], max[1] - min[1]);
+    let fill = 1.0 - 2.0 * FIT_MARGIN;
+    let scale = match (bw > 1e-9, bh > 1e-9) {
+        (true, true) => (w * fill / bw).min(h * fill / bh),
+        (true, false) => w * fill / bw,
+        (false, true) => h * fill / bh,
+        // nothing drawn, or a single point
+        _ => 1.0,
+    };
+    let center = if min[0].is_finite() {
+        [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0]
+    } else {
+        [0.0, 0.0]
+    };
+    move |[x, y]| {
+        [
+            (x - center[0]) * scale + w / 2.0,
+            (y - center[1]) * scale + h / 2.0,
+        ]
+    }
+}
+
+fn create(path: &str) -> Result<BufWriter<File>, String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("{path}: {e}"))
+}
+
+/// A PNG encoder writing the header to the file, animated if there is more than one frame
+fn png_writer(
+    path: &str,
+    frames: usize,
+    options: &ExportOptions,
+) -> Result<png::Writer<BufWriter<File>>, String> {
+    let [w, h] = options.size;
+    let mut encoder = png::Encoder::new(create(path)?, w, h);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if frames > 1 {
+        encoder
+            .set_animated(frames as u32, 0)
+            .map_err(|e| e.to_string())?;
+        let delay = (1000.0 / options.fps).round().clamp(1.0, u16::MAX as f64) as u16;
+        encoder
+            .set_frame_delay(delay, 1000)
+            .map_err(|e| e.to_string())?;
+    }
+    encoder.write_header().map_err(|e| format!("{path}: {e}"))
+}
+
+/// Where the frames go as they are rasterized
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(png::Writer<BufWriter<File>>),
+    /// numbered PNG files after the stem of the path
+    PngSequence {
+        stem: String,
+        digits: usize,
+    },
+}
+
+impl Sink {
+    fn new(path: &str, options: &ExportOptions) -> Result<Self, String> {
+        Ok(match options.format {
+            ExportFormat::Gif => {
+                let [w, h] = options.size;
+                let (w, h) = (
+                    u16::try_from(w).map_err(|_| "GIF frames are at most 65535 pixels wide")?,
+                    u16::try_from(h).map_err(|_| "GIF frames are at most 65535 pixels high")?,
+                );
+                let mut encoder =
+                    gif::Encoder::new(create(path)?, w, h, &[]).map_err(|e| e.to_string())?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(|e| e.to_string())?;
+                Sink::Gif(encoder)
+            }
+            ExportFormat::Apng => Sink::Apng(png_writer(path, options.frames, options)?),
+            ExportFormat::PngSequence => Sink::PngSequence {
+                stem: Path::new(path).with_extension("").display().to_string(),
+                digits: options.frames.to_string().len().max(4),
+            },
+        })
+    }
+
+    fn write(&mut self, i: usize, rgba: &mut [u8], options: &ExportOptions) -> Result<(), String> {
+        match self {
+            Sink::Gif(encoder) => {
+                let [w, h] = options.size;
+                let mut frame = gif::Frame::from_rgba_speed(w as u16, h as u16, rgba, 10);
+                // GIF delays are in hundredths of a second
+                frame.delay = (100.0 / options.fps).round().max(1.0) as u16;
+                frame.dispose = gif::DisposalMethod::Background;
+                encoder.write_frame(&frame).map_err(|e| e.to_string())
+            }
+            Sink::Apng(writer) => writer.write_image_data(rgba).map_err(|e| e.to_string()),
+            Sink::PngSequence { stem, digits } => {
+                let path = format!("{stem}_{i:0digits$}.png", digits = *digits);
+                let mut writer = png_writer(&path, 1, options)?;
+                writer.write_image_data(rgba).map_err(|e| e.to_string())?;
+                writer.finish().map_err(|e| format!("{path}: {e}"))
+            }
+        }
+    }
+
+    /// Write what the encoder still holds, returns the count of files written
+    fn finish(self, frames: usize) -> Result<usize, String> {
+        match self {
+            Sink::Gif(encoder) => {
+                let mut file = encoder.into_inner().map_err(|e| e.to_string())?;
+                file.flush().map_err(|e| e.to_string())?;
+                Ok(1)
+            }
+            Sink::Apng(writer) => writer.finish().map(|_| 1).map_err(|e| e.to_string()),
+            Sink::PngSequence { .. } => Ok(frames),
+        }
+    }
+}
+
+/// Render the frames of the code and write them to `path` one by one as they are encoded.
+/// `on_frame` is told the count of frames done and stops the export by returning false.
+/// Returns the count of files written, a PNG sequence numbers the frames after the stem of `path`
+pub fn export_frames(
+    code: &str,
+    options: &ExportOptions,
+    path: &str,
+    mut on_frame: impl FnMut(usize) -> bool,
+) -> Result<usize, ExportError> {
+    if options.frames == 0 {
+        return Err(ExportError::Write("No frames to export".to_owned()));
+    }
+    if options.fps <= 0.0 {
+        return Err(ExportError::Write("The fps must be positive".to_owned()));
+    }
+    if options.morph.as_ref().is_some_and(|m| m.duration <= 0.0) {
+        return Err(ExportError::Write(
+            "The morph duration must be positive".to_owned(),
+        ));
+    }
+
+    let mut source = FrameSource::new(code, options)?;
+    // fitting needs the points of all frames before the first one is drawn, the lines of
+    // the frames are kept then so that they are made once
+    let mut cached = if options.fit {
+        (0..options.frames)
+            .map(|i| source.frame(i))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)?
+    } else {
+        None
+    };
+    let map = cached.as_ref().map(|frames| {
+        let mut bounds = [[f64::INFINITY; 2], [f64::NEG_INFINITY; 2]];
+        frames
+            .iter()
+            .for_each(|lines| grow_bounds(&mut bounds, lines));
+        fit_map(bounds, options.size)
+    });
+
+    let mut sink = Sink::new(path, options).map_err(ExportError::Write)?;
+    let colors = [options.background, options.color];
+    for i in 0..options.frames {
+        let lines = match &mut cached {
+            Some(frames) => std::mem::take(&mut frames[i]),
+            None => source.frame(i)?,
+        };
+        let mut rgba = rasterize_rgba(&lines, options.size, options.lcd_coords, colors, |p| {
+            map.as_ref().map_or(p, |map| map(p))
+        });
+        sink.write(i, &mut rgba, options)
+            .map_err(ExportError::Write)?;
+        if !on_frame(i + 1) {
+            return Err(ExportError::Cancelled);
+        }
+    }
+    sink.finish(options.frames).map_err(ExportError::Write)
+}
@@ -0,0 +1,195 @@
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{Command, IParser, ParseError, Span};
+
+/// Self
+use super::convert::fmt_num_precision;
+use super::{CodeParser, VecLineGen};
+
+/// Decimals kept by the formatter if not told otherwise
+pub const DEFAULT_FORMAT_PRECISION: usize = 6;
+
+enum Item<'a> {
+    Command(&'a Command),
+    Comment(Span, String),
+}
+
+#[derive(Default)]
+struct Line {
+    /// op name and params, empty for a comment line
+    cells: Vec<String>,
+    comment: Option<String>,
+    blank_before: bool,
+}
+
+fn fmt_string(s: &str) -> String {
+    let mut res = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn command_cells(cmd: &Command, precision: usize) -> Vec<String> {
+    let mut cells = vec![format!("{},", cmd.dsc.name()[0].to_lowercase())];
+    cells.extend(cmd.argv.iter().enumerate().map(|(i, v)| {
+        if let Some(arg) = cmd.timed_args.iter().find(|arg| arg.index == i) {
+            arg.text.clone()
+        } else if let Some(s) = v.try_cast_ref::<String>() {
+            fmt_string(s)
+        } else {
+            fmt_num_precision(*v.cast_ref::<f64>(), precision)
+        }
+    }));
+    cells
+}
+
+/// Re-emit the code in canonical form: one command per line, lowercase op names without aliases,
+/// numbers with at most `precision` decimals and params aligned in columns. Comments are kept,
+/// and blank lines between blocks are squeezed into one.
+pub fn format_code(code: &str, precision: usize) -> Result<String, ParseError> {
+    let mut generator = VecLineGen::default();
+    let mut parser = CodeParser::new(AnyData::new(code.to_owned()), &mut generator);
+    parser.parse()?;
+    let comments = parser.comments();
+
+    let mut items = generator
+        .ops()
+        .iter()
+        .map(Item::Command)
+        .chain(comments.into_iter().map(|(span, c)| Item::Comment(span, c)))
+        .collect::<Vec<_>>();
+    items.sort_by_key(|item| match item {
+        Item::Command(cmd) => cmd.span.0.pos,
+        Item::Comment(span, _) => span.0.pos,
+    });
+
+    let mut lines: Vec<Line> = vec![];
+    // row where the last item ends, and whether it is a command
+    let mut last_row = None;
+    let mut last_is_command = false;
+    for item in items {
+        match item {
+            Item::Command(cmd) => {
+                lines.push(Line {
+                    cells: command_cells(cmd, precision),
+                    comment: None,
+                    blank_before: last_row.is_some_and(|r| cmd.span.0.row > r + 1),
+                });
+                last_row = Some(cmd.span.1.row);
+                last_is_command = true;
+            }
+            Item::Comment(span, c) => {
+                let c = c.trim_end().to_owned();
+                let end_row = span.0.row + c.lines().count().max(1) - 1;
+                let trailing = last_is_command && last_row == Some(span.0.row);
+                match lines.last_mut() {
+                    Some(line) if trailing && line.comment.is_none() => {
+                        line.comment = Some(c);
+                    }
+                    _ => lines.push(Line {
+                        cells: vec![],
+                        comment: Some(c),
+                        blank_before: last_row.is_some_and(|r| span.0.row > r + 1),
+                    }),
+                }
+                last_row = Some(end_row);
+                last_is_command = false;
+            }
+        }
+    }
+
+    // commands next to each other with op names of the same length, like move/line/quad/cubi,
+    // make up a block, and share the column widths
+    let mut widths_of_lines = vec![vec![]; lines.len()];
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].cells.is_empty() {
+            i += 1;
+            continue;
+        }
+        let mut j = i + 1;
+        while j < lines.len()
+            && !lines[j].cells.is_empty()
+            && !lines[j].blank_before
+            && lines[j].cells[0].len() == lines[i].cells[0].len()
+        {
+            j += 1;
+        }
+
+        let mut widths: Vec<usize> = vec![];
+        for line in &lines[i..j] {
+            for (k, cell) in line.cells.iter().enumerate() {
+                let len = cell.chars().count();
+                match widths.get_mut(k) {
+                    Some(w) => *w = (*w).max(len),
+                    None => widths.push(len),
+                }
+            }
+        }
+        widths_of_lines[i..j].fill(widths);
+        i = j;
+    }
+
+    let mut res = String::new();
+    for (line, widths) in lines.iter().zip(widths_of_lines) {
+        if line.blank_before {
+            res.push('\n');
+        }
+
+        let mut text = String::new();
+        for (j, cell) in line.cells.iter().enumerate() {
+            if j == 0 {
+                text.push_str(&format!("{:<width$}", cell, width = widths[j]));
+            } else {
+                text.push_str(&format!(" {:>width$},", cell, width = widths[j]));
+            }
+        }
+        if let Some(comment) = &line.comment {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(comment);
+        }
+        res.push_str(text.trim_end());
+        res.push('\n');
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::convert::parse;
+    use super::*;
+    use crate::sample_codes_list::SAMPLE_CODES_LIST;
+
+    fn commands(code: &str) -> Vec<(&'static str, usize)> {
+        parse(code)
+            .unwrap()
+            .ops()
+            .iter()
+            .map(|op| (op.dsc.name()[0], op.dsc.argc()))
+            .collect()
+    }
+
+    #[test]
+    fn format_code_is_idempotent() {
+        for (name, code) in SAMPLE_CODES_LIST {
+            let formatted = format_code(code, DEFAULT_FORMAT_PRECISION).unwrap();
+            assert_eq!(
+                format_code(&formatted, DEFAULT_FORMAT_PRECISION).unwrap(),
+                formatted,
+                "{name}"
+            );
+            assert_eq!(commands(&formatted), commands(code), "{name}");
+        }
+    }
+}
@@ -1,5 +1,7 @@
 /// Stds
-use std::fmt::Debug;
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::mem::take;
 use std::ops::Range;
 
 /// 3rds
@@ -7,10 +9,15 @@ use getset::{CopyGetters, Getters, MutGetters, Setters};
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::{Command, ICommandSyntax, IVisData, IVisDataGenerator};
+use crate::interfaces::{
+    Command, Diagnostic, DiagnosticLevel, ICommandSyntax, IVisData, IVisDataGenerator,
+};
 
 /// Self
-use super::ops::GenerateCtx;
+use super::curve::Curve;
+use super::lint::{lint_paths, lint_transforms};
+use super::ops::op_clip::clip_polyline;
+use super::ops::{GenerateCtx, GraphicsState, calc_trans_stack, inverse_matrix};
 use super::syntax::CommonVecOpSyntax;
 
 #[derive(Getters, Setters, MutGetters, CopyGetters, Default, Debug, Copy, Clone, PartialEq)]
@@ -64,53 +71,310 @@ impl IVisData for VecLineData {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Signed fixed point format Qm.n, m integer bits with the sign included and n fraction bits
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FixedPoint {
+    pub int_bits: u32,
+    pub frac_bits: u32,
+}
+
+impl FixedPoint {
+    /// Round to the nearest value the format holds, saturating on overflow
+    pub fn quantize(&self, v: f64) -> f64 {
+        let scale = 2f64.powi(self.frac_bits as i32);
+        let max = 2f64.powi(self.int_bits as i32 - 1);
+        ((v * scale).round() / scale).clamp(-max, max - 1.0 / scale)
+    }
+}
+
+/// Keep the drawn subpaths in the ctx, for ops working on the whole path like OFFSET
+pub(super) fn record_path(ctx: &mut GenerateCtx, points: &[VecLineData]) {
+    let Some(first) = points.first() else {
+        return;
+    };
+    let grouping = ctx.grouping();
+    let points = points.iter().map(|v| [v.x, v.y]);
+    match ctx.path_mut().last_mut() {
+        Some(contour) if grouping && contour.last() == Some(&[first.x, first.y]) => {
+            contour.extend(points.skip(1))
+        }
+        _ => ctx.path_mut().push(points.collect()),
+    }
+}
+
+#[derive(Getters, CopyGetters, Setters, Debug, Clone)]
 pub struct VecLineGen {
+    #[getset(get = "pub")]
     ops: Vec<Command>,
+
+    /// quantize every point after all transforms, like a fixed point backend does
+    #[getset(get_copy = "pub", set = "pub")]
+    fixed_point: Option<FixedPoint>,
+
+    /// what every untimed op did the last time it was operated, kept between frames
+    cache: RefCell<Vec<Option<CachedOp>>>,
+}
+
+/// What one expanded op did to the ctx, replayed instead of operating it again
+#[derive(Clone)]
+struct OpEffect {
+    points: Vec<VecLineData>,
+    state: GraphicsState,
+    saved_states: Vec<GraphicsState>,
+    grouping: bool,
+    curves: Vec<Curve>,
+    extra_lines: Vec<Vec<VecLineData>>,
+    diagnostics: Vec<String>,
+    /// the op took the subpaths drawn so far, like OFFSET does
+    took_path: bool,
+}
+
+impl OpEffect {
+    /// Put what the op did into the ctx, returns the points it drew
+    fn replay(&self, ctx: &mut GenerateCtx) -> Vec<VecLineData> {
+        *ctx.state_mut() = self.state.clone();
+        *ctx.saved_states_mut() = self.saved_states.clone();
+        ctx.set_grouping(self.grouping);
+        ctx.curves_mut().extend_from_slice(&self.curves);
+        ctx.extra_lines_mut().extend_from_slice(&self.extra_lines);
+        ctx.diagnostics_mut().extend_from_slice(&self.diagnostics);
+        if self.took_path {
+            ctx.path_mut().clear();
+        }
+        self.points.clone()
+    }
+}
+
+/// The effects of an untimed op and the ctx it was operated in, an op reads nothing else
+/// besides the subpaths when it takes them
+#[derive(Clone)]
+struct CachedOp {
+    grouping: bool,
+    state: GraphicsState,
+    saved_states: Vec<GraphicsState>,
+    effects: Vec<OpEffect>,
+}
+
+impl Debug for CachedOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedOp")
+            .field("effects", &self.effects.len())
+            .finish_non_exhaustive()
+    }
 }
 
 impl VecLineGen {
     pub fn new(ops: Vec<Command>) -> Self {
-        Self { ops }
+        Self {
+            ops,
+            fixed_point: None,
+            cache: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn is_timed(&self) -> bool {
+        self.ops.iter().any(Command::is_timed)
+    }
+
+    /// Evaluate the params written with `t` again, only the timed ops change
+    pub fn set_time(&mut self, t: f64) {
+        self.ops.iter_mut().for_each(|op| op.set_time(t));
+    }
+
+    /// Operate the op at `i` expanded, calling `each` with what every expanded op draws.
+    /// An untimed op operated in the same ctx as last time is replayed from the cache,
+    /// `path_changed` tells whether the subpaths differ from last time and is kept up to date
+    fn operate_op(
+        &self,
+        i: usize,
+        gen_ctx: &mut AnyData,
+        path_changed: &mut bool,
+        mut each: impl FnMut(&mut AnyData, Vec<VecLineData>),
+    ) {
+        let op = &self.ops[i];
+        let mut cache = self.cache.borrow_mut();
+        if cache.len() < self.ops.len() {
+            cache.resize_with(self.ops.len(), || None);
+        }
+
+        let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+        let hit = cache[i].as_ref().filter(|cached| {
+            cached.grouping == ctx.grouping()
+                && cached.state == *ctx.state()
+                && cached.saved_states == *ctx.saved_states()
+                && !(*path_changed && cached.effects.iter().any(|e| e.took_path))
+        });
+        if let Some(cached) = hit {
+            for effect in &cached.effects {
+                let points = effect.replay(gen_ctx.cast_mut::<GenerateCtx>());
+                if effect.took_path {
+                    *path_changed = false;
+                }
+                each(gen_ctx, points);
+            }
+            return;
+        }
+
+        let input = (!op.is_timed()).then(|| {
+            (
+                ctx.grouping(),
+                ctx.state().clone(),
+                ctx.saved_states().clone(),
+            )
+        });
+        let mut effects = vec![];
+        for op in op.expand() {
+            let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+            let drawn = (
+                ctx.curves().len(),
+                ctx.extra_lines().len(),
+                ctx.diagnostics().len(),
+            );
+            let had_path = !ctx.path().is_empty();
+
+            let points = AnyData::convert_from_vec::<VecLineData>(op.operate(gen_ctx));
+
+            let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+            let effect = OpEffect {
+                points,
+                state: ctx.state().clone(),
+                saved_states: ctx.saved_states().clone(),
+                grouping: ctx.grouping(),
+                curves: ctx.curves()[drawn.0..].to_vec(),
+                extra_lines: ctx.extra_lines()[drawn.1..].to_vec(),
+                diagnostics: ctx.diagnostics()[drawn.2..].to_vec(),
+                took_path: had_path && ctx.path().is_empty(),
+            };
+            if effect.took_path {
+                *path_changed = false;
+            } else if !effect.points.is_empty() {
+                *path_changed = true;
+            }
+            each(gen_ctx, effect.points.clone());
+            effects.push(effect);
+        }
+
+        cache[i] = input.map(|(grouping, state, saved_states)| CachedOp {
+            grouping,
+            state,
+            saved_states,
+            effects,
+        });
+    }
+
+    /// Operate the ops in `range` without drawing, the ctx is left as `generate` leaves it
+    pub fn run(&self, range: Range<i64>) -> GenerateCtx {
+        let mut gen_ctx = AnyData::new(GenerateCtx::default());
+        let mut path_changed = false;
+
+        for i in (0..self.ops.len()).filter(|i| range.contains(&(*i as i64))) {
+            self.operate_op(i, &mut gen_ctx, &mut path_changed, |gen_ctx, converted| {
+                let ctx = gen_ctx.cast_mut::<GenerateCtx>();
+                record_path(ctx, &converted);
+                ctx.extra_lines_mut().clear();
+            });
+        }
+        gen_ctx.cast::<GenerateCtx>()
+    }
+
+    /// The matrix taking drawn points back to the params of an op added at the end,
+    /// None if the transforms there are singular
+    pub fn drawn_to_params(&self) -> Option<[[f64; 3]; 3]> {
+        let ctx = self.run(0..self.ops.len() as i64);
+        let state = ctx.state();
+        inverse_matrix(calc_trans_stack(&vec![
+            state.current_world_trans(),
+            state.current_local_trans(),
+        ]))
+    }
+}
+
+/// Put what one expanded op drew into the lines
+fn draw_op(
+    converted: Vec<VecLineData>,
+    gen_ctx: &mut AnyData,
+    points_total: &mut Vec<Vec<Box<dyn IVisData>>>,
+    points: &mut Vec<Box<dyn IVisData>>,
+) {
+    let ctx = gen_ctx.cast_mut::<GenerateCtx>();
+    record_path(ctx, &converted);
+    for line in take(ctx.extra_lines_mut()) {
+        let line = line.iter().map(|v| [v.x, v.y]).collect::<Vec<_>>();
+        let pieces = match ctx.state().clip() {
+            Some(clip) => clip_polyline(&line, clip),
+            None => vec![line],
+        };
+        for piece in pieces {
+            if !points.is_empty() {
+                points_total.push(take(points));
+            }
+            points_total.push(
+                piece
+                    .into_iter()
+                    .map(|[x, y]| {
+                        let res: Box<dyn IVisData> = Box::new(VecLineData::new(x, y));
+                        res
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+    if let Some(clip) = ctx.state().clip()
+        && ctx.grouping()
+    {
+        let converted = converted.iter().map(|v| [v.x, v.y]).collect::<Vec<_>>();
+        for piece in clip_polyline(&converted, clip) {
+            let start = VecLineData::new(piece[0][0], piece[0][1]);
+            // a piece not joining the last one starts a new line
+            if points.last().is_some_and(|p| !p.is_same(&start)) {
+                points_total.push(take(points));
+            }
+            points.extend(piece.into_iter().map(|[x, y]| {
+                let res: Box<dyn IVisData> = Box::new(VecLineData::new(x, y));
+                res
+            }));
+        }
+        return;
     }
+    if ctx.grouping() {
+        points.append(
+            &mut converted
+                .iter()
+                .map(|v| {
+                    let res: Box<dyn IVisData> = Box::new(*v);
+                    res
+                })
+                .collect(),
+        );
+        return;
+    }
+    points_total.push(take(points));
 }
 
 impl IVisDataGenerator for VecLineGen {
     fn add(&mut self, op: Command) {
         self.ops.push(op);
+        self.cache.borrow_mut().clear();
     }
 
     fn generate(&self, range: Range<i64>) -> Vec<Vec<Box<dyn IVisData>>> {
         let mut gen_ctx = AnyData::new(GenerateCtx::default());
-        let mut points_total = vec![];
-        let mut points: Vec<Box<dyn IVisData>> = vec![];
-        let mut counter = 0i64;
-
         let p: Box<dyn IVisData> = Box::new(VecLineData::new(0.0, 0.0));
-        points_total.push(vec![p]);
+        let mut points_total = vec![vec![p]];
+        let mut points = vec![];
+        let mut path_changed = false;
+        let mut counter = 0;
 
-        for op in &self.ops {
+        for i in 0..self.ops.len() {
             if !range.contains(&counter) {
                 continue;
             }
 
-            let converted = AnyData::convert_from_vec::<VecLineData>(op.operate(&mut gen_ctx));
-
-            if gen_ctx.cast_ref::<GenerateCtx>().grouping() {
-                points.append(
-                    &mut converted
-                        .iter()
-                        .map(|v| {
-                            let res: Box<dyn IVisData> = Box::new(*v);
-                            res
-                        })
-                        .collect(),
-                );
-                counter += 1;
-                continue;
-            }
-            points_total.push(points);
-            points = vec![];
+            self.operate_op(i, &mut gen_ctx, &mut path_changed, |gen_ctx, converted| {
+                draw_op(converted, gen_ctx, &mut points_total, &mut points);
+            });
 
             counter += 1;
         }
@@ -118,16 +382,47 @@ impl IVisDataGenerator for VecLineGen {
             points_total.push(points);
         }
 
-        let world_matrix = gen_ctx.cast_ref::<GenerateCtx>().current_world_trans();
+        let world_matrix = gen_ctx
+            .cast_ref::<GenerateCtx>()
+            .state()
+            .current_world_trans();
         points_total.iter_mut().for_each(|p| {
             p.iter_mut().for_each(|x| {
-                *x = Box::new(x.matrix(world_matrix).cast::<VecLineData>());
+                let mut v = x.matrix(world_matrix).cast::<VecLineData>();
+                if let Some(fixed_point) = self.fixed_point {
+                    v = VecLineData::new(fixed_point.quantize(v.x), fixed_point.quantize(v.y));
+                }
+                *x = Box::new(v);
             })
         });
 
         points_total
     }
 
+    fn diagnose(&self, canvas: Option<[f64; 4]>) -> Vec<Diagnostic> {
+        let mut gen_ctx = AnyData::new(GenerateCtx::default());
+        let mut diagnostics = vec![];
+
+        for op in &self.ops {
+            op.expand().iter().for_each(|op| {
+                op.operate(&mut gen_ctx);
+            });
+
+            let ctx = gen_ctx.cast_mut::<GenerateCtx>();
+            diagnostics.extend(ctx.diagnostics_mut().drain(..).map(|msg| Diagnostic {
+                level: DiagnosticLevel::Warning,
+                msg,
+                span: op.span.clone(),
+                fix: None,
+            }));
+        }
+
+        diagnostics.extend(lint_transforms(&self.ops));
+        diagnostics.extend(lint_paths(self, canvas));
+        diagnostics.sort_by_key(|d| d.span.0.pos);
+        diagnostics
+    }
+
     fn len(&self) -> usize {
         self.ops.len()
     }
@@ -142,3 +437,40 @@ impl Default for VecLineGen {
         VecLineGen::new(vec![])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common_vec_op::convert::parse;
+
+    fn drawn(generator: &VecLineGen) -> Vec<Vec<[f64; 2]>> {
+        generator
+            .generate(0..generator.len() as i64)
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|p| {
+                        let [x, y] = p.pos();
+                        [*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cached_frames_draw_as_fresh_ones() {
+        let code = "rotate, t*pi,\nmove, 0, 0,\nline, 10, 0,\npop_trans,\nmove, 0, 0,\nline, 10*t, 5,\nline, 0, 10,\noffset, 1,\ntext, 0, 0, 10, \"a\",\nclip, 0, 0, 5, 5,\nline, 20, 20,\n";
+        let mut cached = parse(code).unwrap();
+        for t in [0.0, 0.5, 0.5, 1.0, 0.25] {
+            cached.set_time(t);
+            let mut fresh = parse(code).unwrap();
+            fresh.set_time(t);
+            assert_eq!(drawn(&cached), drawn(&fresh), "t = {t}");
+            assert_eq!(
+                cached.run(0..cached.len() as i64).curves().len(),
+                fresh.run(0..fresh.len() as i64).curves().len()
+            );
+        }
+    }
+}
@@ -0,0 +1,152 @@
+/// Stds
+use std::ops::Range;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::Cursor;
+
+/// Self
+use super::VecLineGen;
+use super::curve::Curve;
+use super::ops::GenerateCtx;
+
+/// Curve with the index and the place of the command drawing it
+type Piece = (Curve, usize, Cursor);
+
+/// How a point sits against one subpath, taken as closed
+#[derive(Debug, Clone)]
+pub struct PathHit {
+    /// where the subpath starts in the code
+    pub start: Cursor,
+    /// positive if the subpath goes counterclockwise around the point with y up
+    pub winding: i32,
+}
+
+impl PathHit {
+    pub fn nonzero(&self) -> bool {
+        self.winding != 0
+    }
+
+    pub fn even_odd(&self) -> bool {
+        self.winding % 2 != 0
+    }
+}
+
+/// Point on the path nearest to the tested one
+#[derive(Debug, Clone)]
+pub struct NearestPoint {
+    pub point: [f64; 2],
+    pub distance: f64,
+    /// index of the command drawing the segment
+    pub op: usize,
+    pub cursor: Cursor,
+}
+
+/// Where a point is against the paths drawn, in drawn coordinates
+#[derive(Debug, Clone)]
+pub struct HitTest {
+    pub point: [f64; 2],
+    /// of all subpaths together, as the whole path is filled
+    pub winding: i32,
+    pub paths: Vec<PathHit>,
+    pub nearest: Option<NearestPoint>,
+}
+
+impl HitTest {
+    pub fn nonzero(&self) -> bool {
+        self.winding != 0
+    }
+
+    pub fn even_odd(&self) -> bool {
+        self.winding % 2 != 0
+    }
+}
+
+impl VecLineGen {
+    /// Test the point against every subpath drawn by the ops in `range`
+    pub fn hit_test(&self, range: Range<i64>, point: [f64; 2]) -> HitTest {
+        let mut gen_ctx = AnyData::new(GenerateCtx::default());
+        // curves of every subpath, with the command drawing them
+        let mut subpaths: Vec<(Cursor, Vec<Piece>)> = vec![];
+        let mut open = false;
+        for (i, op) in self
+            .ops()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| range.contains(&(*i as i64)))
+        {
+            for op in op.expand() {
+                let drawn = gen_ctx.cast_ref::<GenerateCtx>().curves().len();
+                op.operate(&mut gen_ctx);
+                let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+                match op.dsc.name()[0] {
+                    "MOVE" => {
+                        subpaths.push((op.span.0.clone(), vec![]));
+                        open = true;
+                    }
+                    "END" => open = false,
+                    _ => {}
+                }
+                for curve in &ctx.curves()[drawn..] {
+                    // drawing without a MOVE starts a subpath from the cursor
+                    if !open {
+                        subpaths.push((op.span.0.clone(), vec![]));
+                        open = true;
+                    }
+                    if let Some((_, curves)) = subpaths.last_mut() {
+                        curves.push((*curve, i, op.span.0.clone()));
+                    }
+                }
+            }
+        }
+        let world_matrix = gen_ctx
+            .cast_ref::<GenerateCtx>()
+            .state()
+            .current_world_trans();
+
+        let mut res = HitTest {
+            point,
+            winding: 0,
+            paths: vec![],
+            nearest: None,
+        };
+        for (start, curves) in subpaths {
+            let Some((first, _, _)) = curves.first() else {
+                continue;
+            };
+            let curves = curves
+                .iter()
+                .map(|(c, op, cursor)| (c.transform(world_matrix), *op, cursor))
+                .collect::<Vec<_>>();
+            let (begin, end) = (
+                first.transform(world_matrix).point(0.0),
+                curves
+                    .last()
+                    .map(|(c, _, _)| c.point(1.0))
+                    .unwrap_or_default(),
+            );
+
+            let winding = curves
+                .iter()
+                .map(|(c, _, _)| c.winding(point))
+                .chain([Curve::Line([end, begin]).winding(point)])
+                .sum::<i32>();
+            res.winding += winding;
+            res.paths.push(PathHit { start, winding });
+
+            for (curve, op, cursor) in &curves {
+                let (t, distance) = curve.nearest(point);
+                if res.nearest.as_ref().is_none_or(|n| distance < n.distance) {
+                    res.nearest = Some(NearestPoint {
+                        point: curve.point(t),
+                        distance,
+                        op: *op,
+                        cursor: (*cursor).clone(),
+                    });
+                }
+            }
+        }
+
+        res
+    }
+}
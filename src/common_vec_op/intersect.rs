@@ -0,0 +1,219 @@
+/// Stds
+use std::ops::Range;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::Cursor;
+
+/// Self
+use super::VecLineGen;
+use super::curve::Curve;
+use super::ops::GenerateCtx;
+
+/// Chords every curve is cut into to find the crossings roughly
+const CHORDS_PER_CURVE: usize = 16;
+
+/// Newton steps polishing a rough crossing onto the curves
+const REFINE_STEPS: usize = 8;
+
+/// Place where two segments of the drawn paths cross
+#[derive(Debug, Clone)]
+pub struct Intersection {
+    /// in drawn coordinates
+    pub point: [f64; 2],
+    /// indices of the two commands, the smaller first
+    pub ops: [usize; 2],
+    /// where the two commands are in the code
+    pub cursors: [Cursor; 2],
+    /// both segments belong to the same subpath
+    pub same_subpath: bool,
+}
+
+/// Curve with where it comes from
+struct Piece {
+    curve: Curve,
+    op: usize,
+    cursor: Cursor,
+    subpath: usize,
+}
+
+/// Straight piece of a flattened curve
+struct Chord {
+    piece: usize,
+    index: usize,
+    t: [f64; 2],
+    p: [[f64; 2]; 2],
+}
+
+impl Chord {
+    fn x_range(&self) -> [f64; 2] {
+        [
+            self.p[0][0].min(self.p[1][0]),
+            self.p[0][0].max(self.p[1][0]),
+        ]
+    }
+
+    fn y_range(&self) -> [f64; 2] {
+        [
+            self.p[0][1].min(self.p[1][1]),
+            self.p[0][1].max(self.p[1][1]),
+        ]
+    }
+}
+
+/// Where along the two segments they cross, None for parallel ones
+fn segment_intersection(a: [[f64; 2]; 2], b: [[f64; 2]; 2]) -> Option<[f64; 2]> {
+    let r = [a[1][0] - a[0][0], a[1][1] - a[0][1]];
+    let s = [b[1][0] - b[0][0], b[1][1] - b[0][1]];
+    let denom = r[0] * s[1] - r[1] * s[0];
+    if denom.abs() < 1e-12 {
+        return None;
+    }
+    let q = [b[0][0] - a[0][0], b[0][1] - a[0][1]];
+    let u = (q[0] * s[1] - q[1] * s[0]) / denom;
+    let v = (q[0] * r[1] - q[1] * r[0]) / denom;
+    ((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)).then_some([u, v])
+}
+
+/// Solve a(s) = b(t) with Newton's method from a rough guess
+fn refine(a: &Curve, b: &Curve, [mut s, mut t]: [f64; 2]) -> [f64; 2] {
+    for _ in 0..REFINE_STEPS {
+        let (pa, pb) = (a.point(s), b.point(t));
+        let f = [pa[0] - pb[0], pa[1] - pb[1]];
+        let (da, db) = (a.derivative(s), b.derivative(t));
+        // the jacobian is [da, -db]
+        let det = -da[0] * db[1] + da[1] * db[0];
+        if det.abs() < 1e-12 {
+            break;
+        }
+        let ds = (-db[1] * f[0] + db[0] * f[1]) / det;
+        let dt = (-da[1] * f[0] + da[0] * f[1]) / det;
+        s = (s - ds).clamp(0.0, 1.0);
+        t = (t - dt).clamp(0.0, 1.0);
+    }
+    [s, t]
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+impl VecLineGen {
+    /// Find where the segments drawn by the ops in `range` cross each other,
+    /// segments only touching at an end point they share are left out
+    pub fn intersections(&self, range: Range<i64>) -> Vec<Intersection> {
+        let mut gen_ctx = AnyData::new(GenerateCtx::default());
+        let mut pieces = vec![];
+        let mut subpath = 0;
+        for (i, op) in self
+            .ops()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| range.contains(&(*i as i64)))
+        {
+            for op in op.expand() {
+                if matches!(op.dsc.name()[0], "MOVE" | "END") {
+                    subpath += 1;
+                }
+                let drawn = gen_ctx.cast_ref::<GenerateCtx>().curves().len();
+                op.operate(&mut gen_ctx);
+                let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+                pieces.extend(ctx.curves()[drawn..].iter().map(|curve| Piece {
+                    curve: *curve,
+                    op: i,
+                    cursor: op.span.0.clone(),
+                    subpath,
+                }));
+            }
+        }
+        let world_matrix = gen_ctx
+            .cast_ref::<GenerateCtx>()
+            .state()
+            .current_world_trans();
+        pieces
+            .iter_mut()
+            .for_each(|p| p.curve = p.curve.transform(world_matrix));
+
+        let mut chords = pieces
+            .iter()
+            .enumerate()
+            .flat_map(|(i, piece)| {
+                let n = match piece.curve {
+                    Curve::Line(_) => 1,
+                    _ => CHORDS_PER_CURVE,
+                };
+                (0..n).map(move |k| {
+                    let t = [k as f64 / n as f64, (k + 1) as f64 / n as f64];
+                    Chord {
+                        piece: i,
+                        index: k,
+                        t,
+                        p: t.map(|t| piece.curve.point(t)),
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        chords.sort_by(|a, b| a.x_range()[0].total_cmp(&b.x_range()[0]));
+
+        let mut res: Vec<(usize, usize, Intersection)> = vec![];
+        for (i, a) in chords.iter().enumerate() {
+            let [_, ax1] = a.x_range();
+            let [ay0, ay1] = a.y_range();
+            for b in chords[i + 1..].iter().take_while(|b| b.x_range()[0] <= ax1) {
+                let [by0, by1] = b.y_range();
+                if by0 > ay1 || ay0 > by1 {
+                    continue;
+                }
+                // neighbour chords of one curve meet where they are cut
+                if a.piece == b.piece && a.index.abs_diff(b.index) < 2 {
+                    continue;
+                }
+                let (a, b) = if a.piece <= b.piece { (a, b) } else { (b, a) };
+                let Some([u, v]) = segment_intersection(a.p, b.p) else {
+                    continue;
+                };
+                let (pa, pb) = (&pieces[a.piece], &pieces[b.piece]);
+                let guess = [
+                    a.t[0] + u * (a.t[1] - a.t[0]),
+                    b.t[0] + v * (b.t[1] - b.t[0]),
+                ];
+                let [s, t] = refine(&pa.curve, &pb.curve, guess);
+                let point = pa.curve.point(s);
+                let eps = 1e-6 * pa.curve.size().max(pb.curve.size()).max(1e-9);
+                if distance(point, pb.curve.point(t)) > 1e3 * eps {
+                    continue;
+                }
+
+                let ends = |c: &Curve| [c.point(0.0), c.point(1.0)];
+                let shared_end = ends(&pa.curve).iter().any(|e| {
+                    distance(*e, point) < 1e3 * eps
+                        && ends(&pb.curve).iter().any(|f| distance(*e, *f) < eps)
+                });
+                if shared_end {
+                    continue;
+                }
+                let found = res.iter().any(|(i, j, x)| {
+                    (*i, *j) == (a.piece, b.piece) && distance(x.point, point) < 1e3 * eps
+                });
+                if found {
+                    continue;
+                }
+
+                res.push((
+                    a.piece,
+                    b.piece,
+                    Intersection {
+                        point,
+                        ops: [pa.op, pb.op],
+                        cursors: [pa.cursor.clone(), pb.cursor.clone()],
+                        same_subpath: pa.subpath == pb.subpath,
+                    },
+                ));
+            }
+        }
+
+        let mut res = res.into_iter().map(|(_, _, x)| x).collect::<Vec<_>>();
+        res.sort_by_key(|x| x.ops);
+        res
+    }
+}
@@ -0,0 +1,320 @@
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{
+    Command, Diagnostic, DiagnosticLevel, IVisData, QuickFix, Span, StackEffect,
+};
+
+/// Self
+use super::VecLineData;
+use super::VecLineGen;
+use super::convert::fmt_num;
+use super::ops::{GenerateCtx, calc_trans_stack, inverse_matrix};
+
+const IDENTITY: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+fn warning(msg: String, span: &Span) -> Diagnostic {
+    Diagnostic {
+        level: DiagnosticLevel::Warning,
+        msg,
+        span: span.clone(),
+        fix: None,
+    }
+}
+
+fn warning_with_fix(msg: String, span: &Span, title: &str, text: String) -> Diagnostic {
+    Diagnostic {
+        fix: Some(QuickFix {
+            title: title.to_owned(),
+            edits: vec![(span.clone(), text)],
+        }),
+        ..warning(msg, span)
+    }
+}
+
+fn transform(p: [f64; 2], m: [[f64; 3]; 3]) -> [f64; 2] {
+    let p = VecLineData::new(p[0], p[1]).matrix(m).cast::<VecLineData>();
+    [p.x(), p.y()]
+}
+
+fn command_text(op: &Command, argv: &[f64]) -> String {
+    let mut text = op.dsc.name()[0].to_lowercase();
+    argv.iter().for_each(|v| {
+        text.push_str(", ");
+        text.push_str(&fmt_num(*v));
+    });
+    text
+}
+
+fn lint_matrix(op: &Command, m: [[f64; 3]; 3], diagnostics: &mut Vec<Diagnostic>) {
+    let name = op.dsc.name()[0];
+    let is_same = |a: [[f64; 3]; 3], b: [[f64; 3]; 3]| {
+        a.iter()
+            .flatten()
+            .zip(b.iter().flatten())
+            .all(|(a, b)| (a - b).abs() < 1e-12)
+    };
+
+    if m[2] != [0.0, 0.0, 1.0] {
+        diagnostics.push(warning(
+            format!(
+                "{name} has a non-affine bottom row [{}, {}, {}], points are projected",
+                m[2][0], m[2][1], m[2][2]
+            ),
+            &op.span,
+        ));
+    }
+    if inverse_matrix(m).is_none() {
+        diagnostics.push(warning(
+            format!("{name} has a singular matrix, the path collapses"),
+            &op.span,
+        ));
+    } else if is_same(m, IDENTITY) {
+        diagnostics.push(warning(format!("{name} does nothing"), &op.span));
+    }
+}
+
+/// Check pushes and pops of both transform stacks are balanced, and the pushed matrices make sense
+pub fn lint_transforms(ops: &[Command]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    // spans of the pushes not popped yet
+    let mut local_pushes: Vec<Span> = vec![];
+    let mut world_pushes: Vec<Span> = vec![];
+    let mut saved = vec![];
+
+    for op in ops {
+        match op.dsc.stack_effect(op.argv.clone()) {
+            StackEffect::PushLocal(m) => {
+                lint_matrix(op, m, &mut diagnostics);
+                local_pushes.push(op.span.clone());
+            }
+            StackEffect::PushWorld(m) => {
+                lint_matrix(op, m, &mut diagnostics);
+                world_pushes.push(op.span.clone());
+            }
+            StackEffect::PopLocal => {
+                if local_pushes.pop().is_none() {
+                    diagnostics.push(warning(
+                        "POP_TRANS with nothing pushed on the local stack".to_owned(),
+                        &op.span,
+                    ));
+                }
+            }
+            StackEffect::PopWorld => {
+                if world_pushes.pop().is_none() {
+                    diagnostics.push(warning(
+                        "POP_WORLD_TRANS with nothing pushed on the world stack".to_owned(),
+                        &op.span,
+                    ));
+                }
+            }
+            StackEffect::Save => saved.push((local_pushes.clone(), world_pushes.clone())),
+            StackEffect::Restore => {
+                // an unmatched RESTORE is reported by the op itself
+                if let Some((local, world)) = saved.pop() {
+                    local_pushes = local;
+                    world_pushes = world;
+                }
+            }
+            StackEffect::None => {}
+        }
+    }
+
+    for span in &local_pushes {
+        diagnostics.push(warning(
+            "Local transform pushed without a matching POP_TRANS".to_owned(),
+            span,
+        ));
+    }
+    for span in &world_pushes {
+        diagnostics.push(warning(
+            "World transform pushed without a matching POP_WORLD_TRANS".to_owned(),
+            span,
+        ));
+    }
+
+    diagnostics
+}
+
+/// Check path ops for things that draw nothing or could be written simpler,
+/// and points outside the `canvas` (x, y, w, h)
+pub fn lint_paths(generator: &VecLineGen, canvas: Option<[f64; 4]>) -> Vec<Diagnostic> {
+    let ops = generator.ops();
+    let mut diagnostics = vec![];
+
+    // the world transform in the end is used for all points
+    let world_matrix = generator
+        .run(0..ops.len() as i64)
+        .state()
+        .current_world_trans();
+
+    let mut gen_ctx = AnyData::new(GenerateCtx::default());
+    let mut last_move: Option<&Command> = None;
+    let mut subpath_open = false;
+    for op in ops {
+        let name = op.dsc.name()[0];
+        let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+        let start = [ctx.state().cursor().x, ctx.state().cursor().y];
+        let local_matrix = ctx.state().current_local_trans();
+        let is_path = matches!(name, "MOVE" | "LINE" | "QUAD" | "CUBI" | "CONIC");
+        let argv = if is_path {
+            op.argv.iter().map(|v| *v.cast_ref::<f64>()).collect()
+        } else {
+            vec![]
+        };
+        let points = argv
+            .chunks_exact(2)
+            .map(|p| transform([p[0], p[1]], local_matrix))
+            .collect::<Vec<_>>();
+
+        match name {
+            "MOVE" => {
+                if let Some(last_move) = last_move {
+                    diagnostics.push(warning_with_fix(
+                        "MOVE is taken over by the next MOVE".to_owned(),
+                        &last_move.span,
+                        "Remove this MOVE",
+                        String::new(),
+                    ));
+                }
+                last_move = Some(op);
+                subpath_open = false;
+            }
+            // anything drawing subpaths uses up the MOVE before it
+            "LINE" | "QUAD" | "CUBI" | "CONIC" | "TEXT" | "OFFSET" => {
+                last_move = None;
+                subpath_open = true;
+            }
+            "END" => {
+                if !subpath_open {
+                    diagnostics.push(warning_with_fix(
+                        format!("{} without an open subpath", op.name),
+                        &op.span,
+                        &format!("Remove this {}", op.name),
+                        String::new(),
+                    ));
+                }
+                last_move = None;
+                subpath_open = false;
+            }
+            _ => {}
+        }
+
+        if name == "LINE" && points[0] == start {
+            diagnostics.push(warning_with_fix(
+                "LINE of zero length".to_owned(),
+                &op.span,
+                "Remove this LINE",
+                String::new(),
+            ));
+        }
+
+        if name == "CUBI" && is_straight(start, &points) {
+            diagnostics.push(warning_with_fix(
+                format!(
+                    "{} with control points on the chord is a straight line",
+                    op.name
+                ),
+                &op.span,
+                "Replace with LINE",
+                format!("line, {}, {}", fmt_num(argv[4]), fmt_num(argv[5])),
+            ));
+        }
+
+        if let Some([x, y, w, h]) = canvas
+            && is_path
+        {
+            let matrix = calc_trans_stack(&vec![world_matrix, local_matrix]);
+            let inside = |p: [f64; 2]| x <= p[0] && p[0] <= x + w && y <= p[1] && p[1] <= y + h;
+            let final_points = argv
+                .chunks_exact(2)
+                .map(|p| transform([p[0], p[1]], matrix))
+                .collect::<Vec<_>>();
+
+            if let Some(p) = final_points.iter().find(|p| !inside(**p)) {
+                let mut diagnostic = warning(
+                    format!(
+                        "Point ({}, {}) is outside the canvas",
+                        fmt_num(p[0]),
+                        fmt_num(p[1])
+                    ),
+                    &op.span,
+                );
+                // clamping control points would change the curve, so only lines get the fix
+                if let Some(inv) = inverse_matrix(matrix)
+                    && matches!(name, "MOVE" | "LINE")
+                {
+                    let mut clamped = argv.clone();
+                    final_points.iter().enumerate().for_each(|(i, p)| {
+                        let p = [p[0].clamp(x, x + w), p[1].clamp(y, y + h)];
+                        let p = transform(p, inv);
+                        clamped[i * 2] = p[0];
+                        clamped[i * 2 + 1] = p[1];
+                    });
+                    diagnostic.fix = Some(QuickFix {
+                        title: "Clamp into the canvas".to_owned(),
+                        edits: vec![(op.span.clone(), command_text(op, &clamped))],
+                    });
+                }
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        op.expand().iter().for_each(|op| {
+            op.operate(&mut gen_ctx);
+        });
+    }
+
+    diagnostics
+}
+
+/// Whether a cubic from `start` through `points` (c1, c2, end) is a straight line,
+/// the control points have to lie on the chord, otherwise the curve goes beyond the ends
+fn is_straight(start: [f64; 2], points: &[[f64; 2]]) -> bool {
+    let end = points[2];
+    let chord = [end[0] - start[0], end[1] - start[1]];
+    let len2 = chord[0] * chord[0] + chord[1] * chord[1];
+    if len2 == 0.0 {
+        return false;
+    }
+
+    points[..2].iter().all(|c| {
+        let v = [c[0] - start[0], c[1] - start[1]];
+        let cross = v[0] * chord[1] - v[1] * chord[0];
+        let t = (v[0] * chord[0] + v[1] * chord[1]) / len2;
+        (cross / len2.sqrt()).abs() < 1e-6 && (0.0..=1.0).contains(&t)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common_vec_op::convert::parse;
+
+    fn lint(code: &str, canvas: Option<[f64; 4]>) -> Vec<Diagnostic> {
+        lint_paths(&parse(code).unwrap(), canvas)
+    }
+
+    #[test]
+    fn text_uses_up_the_move() {
+        assert!(lint("move, 0, 0,\ntext, 0, 0, 10, \"a\",\nmove, 5, 5,\n", None).is_empty());
+        assert_eq!(lint("move, 0, 0,\nmove, 5, 5,\n", None).len(), 1);
+    }
+
+    #[test]
+    fn close_is_named_as_written() {
+        let diagnostics = lint("close,\n", None);
+        assert_eq!(diagnostics[0].msg, "CLOSE without an open subpath");
+        let diagnostics = lint("end,\n", None);
+        assert_eq!(diagnostics[0].msg, "END without an open subpath");
+    }
+
+    #[test]
+    fn only_lines_are_clamped_into_the_canvas() {
+        let canvas = Some([0.0, 0.0, 10.0, 10.0]);
+        let diagnostics = lint("move, 0, 0,\nline, 20, 5,\nquad, 30, 5, 5, 5,\n", canvas);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].fix.is_some());
+        assert!(diagnostics[1].fix.is_none());
+    }
+}
@@ -0,0 +1,172 @@
+/// Stds
+use std::ops::Range;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{Cursor, IVisData};
+
+/// Self
+use super::curve::Curve;
+use super::ops::GenerateCtx;
+use super::{VecLineData, VecLineGen};
+
+/// Geometry of a subpath, or of the whole program, in drawn coordinates
+#[derive(Debug, Clone, Default)]
+pub struct Measure {
+    /// where it starts in the code
+    pub start: Cursor,
+    pub length: f64,
+    /// positive if counterclockwise with y up, the subpath is closed to its start
+    pub area: f64,
+    /// min x, min y, max x, max y from the curve extrema
+    pub bbox: Option<[f64; 4]>,
+    /// of the area if there is one, otherwise of the path itself
+    pub centroid: Option<[f64; 2]>,
+    /// end and control points
+    pub points: usize,
+
+    /// integrals of x^2 dy and -y^2 dx, and of the point along the path, for the centroids
+    moments: [f64; 2],
+    line_moments: [f64; 2],
+}
+
+impl Measure {
+    fn add(&mut self, seg: &Curve) {
+        self.length += seg.integrate(|_, d| d[0].hypot(d[1]));
+        self.line_moments[0] += seg.integrate(|p, d| p[0] * d[0].hypot(d[1]));
+        self.line_moments[1] += seg.integrate(|p, d| p[1] * d[0].hypot(d[1]));
+        self.close(seg);
+
+        let mut points = vec![seg.point(0.0), seg.point(1.0)];
+        points.extend(seg.extrema().into_iter().map(|t| seg.point(t)));
+        for [x, y] in points {
+            self.bbox = Some(match self.bbox {
+                None => [x, y, x, y],
+                Some([x0, y0, x1, y1]) => [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+            });
+        }
+    }
+
+    /// Add a segment only to the area, like the line closing a subpath
+    fn close(&mut self, seg: &Curve) {
+        self.area += seg.integrate(|p, d| p[0] * d[1] - p[1] * d[0]) / 2.0;
+        self.moments[0] += seg.integrate(|p, d| p[0] * p[0] * d[1]) / 2.0;
+        self.moments[1] -= seg.integrate(|p, d| p[1] * p[1] * d[0]) / 2.0;
+    }
+
+    fn finish(&mut self) {
+        self.centroid = if self.area.abs() > 1e-9 {
+            Some([self.moments[0] / self.area, self.moments[1] / self.area])
+        } else if self.length > 0.0 {
+            Some([
+                self.line_moments[0] / self.length,
+                self.line_moments[1] / self.length,
+            ])
+        } else {
+            None
+        };
+    }
+
+    /// Put measures of subpaths together
+    pub fn merge(measures: &[Measure]) -> Measure {
+        let mut res = Measure::default();
+        for m in measures {
+            res.length += m.length;
+            res.area += m.area;
+            res.points += m.points;
+            (0..2).for_each(|i| {
+                res.moments[i] += m.moments[i];
+                res.line_moments[i] += m.line_moments[i];
+            });
+            res.bbox = match (res.bbox, m.bbox) {
+                (Some(a), Some(b)) => Some([
+                    a[0].min(b[0]),
+                    a[1].min(b[1]),
+                    a[2].max(b[2]),
+                    a[3].max(b[3]),
+                ]),
+                (a, b) => a.or(b),
+            };
+        }
+        res.finish();
+        res
+    }
+}
+
+impl VecLineGen {
+    /// Measure every subpath drawn by the ops in `range`
+    pub fn measure(&self, range: Range<i64>) -> Vec<Measure> {
+        let ops = self
+            .ops()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| range.contains(&(*i as i64)))
+            .flat_map(|(_, op)| op.expand())
+            .collect::<Vec<_>>();
+
+        let world_matrix = self.run(range).state().current_world_trans();
+        let to_drawn = |p: [f64; 2], m: [[f64; 3]; 3]| {
+            let p = VecLineData::new(p[0], p[1]).matrix(m).cast::<VecLineData>();
+            [p.x(), p.y()]
+        };
+
+        let mut measures = vec![];
+        let mut current: Option<(Measure, [f64; 2])> = None;
+        let finish = |current: Option<(Measure, [f64; 2])>, measures: &mut Vec<Measure>, end| {
+            // a MOVE alone draws nothing
+            if let Some((mut m, start)) = current
+                && m.bbox.is_some()
+            {
+                if end != start {
+                    m.close(&Curve::Line([end, start]));
+                }
+                m.finish();
+                measures.push(m);
+            }
+        };
+
+        let mut gen_ctx = AnyData::new(GenerateCtx::default());
+        let mut cursor = [0.0, 0.0];
+        for op in &ops {
+            let drawn = gen_ctx.cast_ref::<GenerateCtx>().curves().len();
+            op.operate(&mut gen_ctx);
+            let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+            let start = cursor;
+            cursor = to_drawn(
+                [ctx.state().cursor().x, ctx.state().cursor().y],
+                world_matrix,
+            );
+
+            match op.dsc.name()[0] {
+                "MOVE" => {
+                    finish(current.take(), &mut measures, start);
+                    let m = Measure {
+                        start: op.span.0.clone(),
+                        points: 1,
+                        ..Default::default()
+                    };
+                    current = Some((m, cursor));
+                }
+                "END" => finish(current.take(), &mut measures, start),
+                _ => {}
+            }
+            for curve in &ctx.curves()[drawn..] {
+                let curve = curve.transform(world_matrix);
+                // drawing without a MOVE starts from the cursor
+                let (m, _) = current.get_or_insert_with(|| {
+                    let m = Measure {
+                        start: op.span.0.clone(),
+                        points: 1,
+                        ..Default::default()
+                    };
+                    (m, curve.point(0.0))
+                });
+                m.add(&curve);
+                m.points += curve.points().len() - 1;
+            }
+        }
+        finish(current.take(), &mut measures, cursor);
+
+        measures
+    }
+}
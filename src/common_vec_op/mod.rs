@@ -1,9 +1,42 @@
+mod analysis;
+mod boolean;
+mod convert;
+mod curve;
+#[cfg(not(target_arch = "wasm32"))]
+mod export;
+mod formatter;
 mod generator;
+mod hit_test;
+mod intersect;
+mod lint;
+mod measure;
+mod morph;
 mod ops;
 mod parser;
+mod raster;
+mod refactor;
+mod reveal;
+mod simplify;
 mod syntax;
 mod visualizer;
 
-pub use generator::{VecLineData, VecLineGen};
+pub use analysis::{CurveAnalysis, QuadApproximation};
+pub use boolean::{BooleanOp, boolean_code, boolean_path};
+pub use convert::{
+    append_commands, bake_transforms, convert_conics, cubics_to_quads, elevate_quads, fmt_num,
+    replace_spans,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use export::{ExportError, ExportFormat, ExportOptions, MorphExport, Sweep, export_frames};
+pub use formatter::{DEFAULT_FORMAT_PRECISION, format_code};
+pub use generator::{FixedPoint, VecLineData, VecLineGen};
+pub use hit_test::HitTest;
+pub use intersect::Intersection;
+pub use measure::Measure;
+pub use morph::{Easing, MorphError, morph_code};
 pub use parser::CodeParser;
-pub use visualizer::CommonVecVisualizer;
+pub use raster::rasterize;
+pub use refactor::{CodeTransform, transform_code};
+pub use reveal::reveal_lines;
+pub use simplify::{fit_curves, fit_points, simplify_lines};
+pub use visualizer::{CommonVecVisualizer, PlotInput};
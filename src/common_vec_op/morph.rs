@@ -0,0 +1,202 @@
+/// Crates
+use crate::interfaces::{Command, ParseError};
+
+/// Self
+use super::VecLineGen;
+use super::convert::{fmt_num, fmt_num_precision, parse, replace_spans};
+use super::formatter::DEFAULT_FORMAT_PRECISION;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease In",
+            Easing::EaseOut => "Ease Out",
+            Easing::EaseInOut => "Ease In Out",
+        }
+    }
+
+    /// Map the progress in [0, 1] onto the eased one, cubic curves
+    pub fn ease(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+        }
+    }
+}
+
+/// Why a morph failed, the cursor is in the program the error is in
+#[derive(Debug, Clone)]
+pub enum MorphError {
+    /// the program morphed from, or where it doesn't match the target
+    Source(ParseError),
+    /// the target program doesn't parse
+    Target(ParseError),
+}
+
+/// The code `from` with every number moved the part `t` of the way to the one in `to`.
+/// Both must have the same commands with the same args, strings must match
+pub fn morph_code(from: &str, to: &str, t: f64) -> Result<String, MorphError> {
+    let source = parse(from).map_err(MorphError::Source)?;
+    let target = parse(to).map_err(MorphError::Target)?;
+    tween(from, &source, &target, t).map_err(MorphError::Source)
+}
+
+fn tween(
+    from: &str,
+    source: &VecLineGen,
+    target: &VecLineGen,
+    t: f64,
+) -> Result<String, ParseError> {
+    let (ops, target_ops) = (source.ops(), target.ops());
+
+    if ops.len() != target_ops.len() {
+        // point at the first command the other program lacks
+        let cursor = ops
+            .get(target_ops.len())
+            .or(ops.last())
+            .map(|op| op.span.0.clone())
+            .unwrap_or_default();
+        return Err(ParseError {
+            msg: format!(
+                "This program has {} commands, the target has {}",
+                ops.len(),
+                target_ops.len()
+            ),
+            cursor,
+        });
+    }
+
+    let mut edits = vec![];
+    for (i, (op, target_op)) in ops.iter().zip(target_ops).enumerate() {
+        let (name, target_name) = (op.dsc.name()[0], target_op.dsc.name()[0]);
+        if name != target_name || op.dsc.argc() != target_op.dsc.argc() {
+            return Err(ParseError {
+                msg: format!(
+                    "Command #{} differs: {name} with {} args here, {target_name} with {} args in the target",
+                    i + 1,
+                    op.dsc.argc(),
+                    target_op.dsc.argc()
+                ),
+                cursor: op.span.0.clone(),
+            });
+        }
+
+        for (j, (a, b)) in op.argv.iter().zip(target_op.argv.iter()).enumerate() {
+            let timed = |op: &Command| {
+                op.timed_args
+                    .iter()
+                    .find(|arg| arg.index == j)
+                    .map(|arg| arg.text.clone())
+            };
+            // expressions of the time are kept as they are
+            let (text, target_text) = (timed(op), timed(target_op));
+            if text.is_some() || target_text.is_some() {
+                if text != target_text {
+                    return Err(ParseError {
+                        msg: format!(
+                            "Command #{} ({name}) arg #{} differs: {} here, {} in the target, only numbers can be tweened",
+                            i + 1,
+                            j + 1,
+                            text.unwrap_or_else(|| fmt_num(*a.cast_ref::<f64>())),
+                            target_text.unwrap_or_else(|| fmt_num(*b.cast_ref::<f64>()))
+                        ),
+                        cursor: op.arg_spans[j].0.clone(),
+                    });
+                }
+                continue;
+            }
+            match (a.try_cast_ref::<f64>(), b.try_cast_ref::<f64>()) {
+                (Some(a), Some(b)) => {
+                    let v = a + (b - a) * t;
+                    if (v - a).abs() > 1e-9 {
+                        edits.push((
+                            op.arg_spans[j].clone(),
+                            fmt_num_precision(v, DEFAULT_FORMAT_PRECISION),
+                        ));
+                    }
+                }
+                _ => {
+                    let (a, b) = (a.try_cast_ref::<String>(), b.try_cast_ref::<String>());
+                    if a != b {
+                        return Err(ParseError {
+                            msg: format!(
+                                "Command #{} ({name}) arg #{} differs: {:?} here, {:?} in the target, only numbers can be tweened",
+                                i + 1,
+                                j + 1,
+                                a.cloned().unwrap_or_default(),
+                                b.cloned().unwrap_or_default()
+                            ),
+                            cursor: op.arg_spans[j].0.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(replace_spans(from, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FROM: &str = "move, 0, 0,\nline, 10, 20,\nline, t * 2, 5,\n";
+    const TO: &str = "move, 10, 0,\nline, 30, -20,\nline, t * 2, 5,\n";
+
+    fn numbers(code: &str) -> Vec<f64> {
+        parse(code)
+            .unwrap()
+            .ops()
+            .iter()
+            .flat_map(|op| op.argv.iter().map(|v| *v.cast_ref::<f64>()))
+            .collect()
+    }
+
+    #[test]
+    fn morph_code_ends_at_both_programs() {
+        assert_eq!(morph_code(FROM, TO, 0.0).unwrap(), FROM);
+        assert_eq!(numbers(&morph_code(FROM, TO, 1.0).unwrap()), numbers(TO));
+        assert_eq!(
+            numbers(&morph_code(FROM, TO, 0.5).unwrap()),
+            [5.0, 0.0, 20.0, 0.0, 0.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn morph_code_tells_which_program_is_wrong() {
+        let Err(MorphError::Source(e)) = morph_code(FROM, "move, 0, 0,\n", 0.5) else {
+            panic!("the count of commands differs");
+        };
+        assert_eq!(e.cursor.row, 1);
+        let Err(MorphError::Target(e)) = morph_code(FROM, "move, 0, 0,\nlin, 1, 1,\n", 0.5) else {
+            panic!("the target doesn't parse");
+        };
+        assert_eq!(e.cursor.row, 1);
+        let Err(MorphError::Source(e)) = morph_code(FROM, &TO.replace("t * 2", "t * 3"), 0.5)
+        else {
+            panic!("the timed args differ");
+        };
+        assert_eq!(e.cursor.row, 2);
+    }
+}
@@ -1,7 +1,10 @@
+pub(crate) mod op_clip;
+pub(crate) mod op_conic;
 pub(crate) mod op_cubi;
 pub(crate) mod op_end;
 pub(crate) mod op_line;
 pub(crate) mod op_move;
+pub(crate) mod op_offset;
 pub(crate) mod op_pop_trans;
 pub(crate) mod op_push_rotate;
 pub(crate) mod op_push_scale;
@@ -9,6 +12,8 @@ pub(crate) mod op_push_skew;
 pub(crate) mod op_push_trans;
 pub(crate) mod op_push_translate;
 pub(crate) mod op_quad;
+pub(crate) mod op_save_restore;
+pub(crate) mod op_text;
 
 /// Stds
 use std::rc::Rc;
@@ -19,28 +24,38 @@ use getset::{CopyGetters, Getters, MutGetters, Setters};
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::{ICommandDescription, ICommandSyntax, IVisData};
+use crate::interfaces::{ICommandDescription, ICommandSyntax, IVisData, StackEffect};
 
 use super::VecLineData;
 /// Self
+use super::curve::Curve;
 use super::syntax::CommonVecOpSyntax;
+use op_clip::CommonOpCLIP;
+use op_conic::CommonOpCONIC;
 use op_cubi::CommonOpCUBI;
 use op_end::CommonOpEND;
 use op_line::CommonOpLINE;
 use op_move::CommonOpMOVE;
+use op_offset::CommonOpOFFSET;
 use op_pop_trans::{CommonOpPopTrans, CommonOpPopWorldTrans};
-use op_push_rotate::{CommonOpPushRotate, CommonOpPushWorldRotate};
-use op_push_scale::{CommonOpPushScale, CommonOpPushWorldScale};
+use op_push_rotate::{
+    CommonOpPushRotate, CommonOpPushRotateAt, CommonOpPushRotateDeg, CommonOpPushRotateDegAt,
+    CommonOpPushWorldRotate, CommonOpPushWorldRotateAt, CommonOpPushWorldRotateDeg,
+    CommonOpPushWorldRotateDegAt,
+};
+use op_push_scale::{
+    CommonOpPushScale, CommonOpPushScaleAt, CommonOpPushWorldScale, CommonOpPushWorldScaleAt,
+};
 use op_push_skew::{CommonOpPushSkew, CommonOpPushWorldSkew};
 use op_push_trans::{CommonOpPushTrans, CommonOpPushWorldTrans};
 use op_push_translate::{CommonOpPushTranslate, CommonOpPushWorldTranslate};
 use op_quad::CommonOpQUAD;
+use op_save_restore::{CommonOpRestore, CommonOpSave};
+use op_text::CommonOpTEXT;
 
-#[derive(Clone, Getters, CopyGetters, MutGetters, Setters)]
-pub struct GenerateCtx {
-    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
-    grouping: bool,
-
+/// What SAVE stores and RESTORE brings back, the rest of the ctx is what has been drawn
+#[derive(Clone, PartialEq, Getters, CopyGetters, MutGetters, Setters)]
+pub struct GraphicsState {
     #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
     cursor: PlotPoint,
 
@@ -55,21 +70,59 @@ pub struct GenerateCtx {
 
     #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
     current_world_trans: [[f64; 3]; 3],
+
+    /// Where the last move went, saved and restored with the rest of the state
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    subpath_start: PlotPoint,
+
+    /// Rect (x, y, w, h) everything drawn is clipped to
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    clip: Option<[f64; 4]>,
 }
 
-impl Default for GenerateCtx {
+impl Default for GraphicsState {
     fn default() -> Self {
         Self {
-            grouping: false,
             cursor: PlotPoint::new(0.0, 0.0),
             local_trans_stack: vec![],
             current_local_trans: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
             world_trans_stack: vec![],
             current_world_trans: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            subpath_start: PlotPoint::new(0.0, 0.0),
+            clip: None,
         }
     }
 }
 
+#[derive(Clone, Default, Getters, CopyGetters, MutGetters, Setters)]
+pub struct GenerateCtx {
+    #[getset(get_copy = "pub", set = "pub", get_mut = "pub")]
+    grouping: bool,
+
+    #[getset(get = "pub", get_mut = "pub")]
+    state: GraphicsState,
+
+    /// States stored by SAVE and waiting for RESTORE
+    #[getset(get = "pub", get_mut = "pub")]
+    saved_states: Vec<GraphicsState>,
+
+    /// Subpaths drawn since the last OFFSET, kept by the generator
+    #[getset(get = "pub", get_mut = "pub")]
+    path: Vec<Vec<[f64; 2]>>,
+
+    /// Analytic curves drawn by the path ops, in local transformed coordinates
+    #[getset(get = "pub", get_mut = "pub")]
+    curves: Vec<Curve>,
+
+    /// Lines drawn apart from the current one, the generator picks them up after every op
+    #[getset(get = "pub", get_mut = "pub")]
+    extra_lines: Vec<Vec<VecLineData>>,
+
+    /// Complaints of ops, the generator picks them up after every op
+    #[getset(get = "pub", get_mut = "pub")]
+    diagnostics: Vec<String>,
+}
+
 pub fn calc_trans_stack(trans_stack: &Vec<[[f64; 3]; 3]>) -> [[f64; 3]; 3] {
     fn mul_matrix(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
         let mut result = [[0.0; 3]; 3];
@@ -92,8 +145,89 @@ pub fn calc_trans_stack(trans_stack: &Vec<[[f64; 3]; 3]>) -> [[f64; 3]; 3] {
     res
 }
 
+/// Push or pop the transform stacks as the op declares, SAVE and RESTORE do more and operate themselves
+pub fn operate_stack_effect(ctx: &mut AnyData, effect: StackEffect) -> Vec<AnyData> {
+    let state = &mut ctx.cast_mut::<GenerateCtx>().state;
+    match effect {
+        StackEffect::PushLocal(matrix) => {
+            state.local_trans_stack.push(matrix);
+            state.current_local_trans = calc_trans_stack(&state.local_trans_stack);
+        }
+        StackEffect::PushWorld(matrix) => {
+            state.world_trans_stack.push(matrix);
+            state.current_world_trans = calc_trans_stack(&state.world_trans_stack);
+        }
+        StackEffect::PopLocal => {
+            if state.local_trans_stack.pop().is_some() {
+                state.current_local_trans = calc_trans_stack(&state.local_trans_stack);
+            }
+        }
+        StackEffect::PopWorld => {
+            if state.world_trans_stack.pop().is_some() {
+                state.current_world_trans = calc_trans_stack(&state.world_trans_stack);
+            }
+        }
+        StackEffect::Save | StackEffect::Restore | StackEffect::None => {}
+    }
+
+    vec![]
+}
+
+/// Move by (tx, ty)
+pub fn translate_matrix(tx: f64, ty: f64) -> [[f64; 3]; 3] {
+    [[1.0, 0.0, tx], [0.0, 1.0, ty], [0.0, 0.0, 1.0]]
+}
+
+/// Rotate counterclockwise by `angle` radians around the origin
+pub fn rotate_matrix(angle: f64) -> [[f64; 3]; 3] {
+    let angle_cos = angle.cos();
+    let angle_sin = angle.sin();
+
+    [
+        [angle_cos, -angle_sin, 0.0],
+        [angle_sin, angle_cos, 0.0],
+        [0.0, 0.0, 1.0],
+    ]
+}
+
+/// Scale by sx and sy from the origin
+pub fn scale_matrix(sx: f64, sy: f64) -> [[f64; 3]; 3] {
+    [[sx, 0.0, 0.0], [0.0, sy, 0.0], [0.0, 0.0, 1.0]]
+}
+
+/// Make the `matrix` work around the pivot (cx, cy) instead of the origin
+pub fn pivot_matrix(matrix: [[f64; 3]; 3], cx: f64, cy: f64) -> [[f64; 3]; 3] {
+    calc_trans_stack(&vec![
+        translate_matrix(cx, cy),
+        matrix,
+        translate_matrix(-cx, -cy),
+    ])
+}
+
+/// The matrix undoing `m`, None if it is singular
+pub fn inverse_matrix(m: [[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+
+    let mut res = [[0.0; 3]; 3];
+    for (i, row) in res.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            // adjugate is the transposed cofactor matrix
+            let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+            let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+            *v = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+        }
+    }
+    Some(res)
+}
+
+/// Transform the argv as (x, y) pairs, a trailing unpaired param is left out
 pub fn process_point(argv: Rc<Vec<AnyData>>, matrix: [[f64; 3]; 3]) -> Vec<f64> {
-    argv.chunks(2)
+    argv.chunks_exact(2)
         .map(|x| {
             VecLineData::new(*x[0].cast_ref(), *x[1].cast_ref())
                 .matrix(matrix)
@@ -114,18 +248,32 @@ impl ICommandSyntax for CommonVecOpSyntax {
             &CommonOpLINE {},
             &CommonOpQUAD {},
             &CommonOpCUBI {},
+            &CommonOpCONIC {},
             &CommonOpEND {},
+            &CommonOpTEXT {},
+            &CommonOpCLIP {},
+            &CommonOpOFFSET {},
+            &CommonOpSave {},
+            &CommonOpRestore {},
             &CommonOpPushTrans {},
             &CommonOpPopTrans {},
             &CommonOpPushScale {},
+            &CommonOpPushScaleAt {},
             &CommonOpPushRotate {},
+            &CommonOpPushRotateDeg {},
+            &CommonOpPushRotateAt {},
+            &CommonOpPushRotateDegAt {},
             &CommonOpPushSkew {},
             &CommonOpPushTranslate {},
             // World Transform Ops
             &CommonOpPushWorldTrans {},
             &CommonOpPopWorldTrans {},
             &CommonOpPushWorldScale {},
+            &CommonOpPushWorldScaleAt {},
             &CommonOpPushWorldRotate {},
+            &CommonOpPushWorldRotateDeg {},
+            &CommonOpPushWorldRotateAt {},
+            &CommonOpPushWorldRotateDegAt {},
             &CommonOpPushWorldSkew {},
             &CommonOpPushWorldTranslate {},
         ]
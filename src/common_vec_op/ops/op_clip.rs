@@ -0,0 +1,89 @@
+/// Stds
+use std::rc::Rc;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::ICommandDescription;
+
+/// Self
+use super::GenerateCtx;
+
+/// Drop everything drawn outside the rect (x, y, w, h) from now on, until a RESTORE.
+/// The rect is not touched by local transforms, world transforms move it along with the points
+pub struct CommonOpCLIP;
+
+impl ICommandDescription for CommonOpCLIP {
+    fn name(&self) -> Vec<&str> {
+        ["CLIP"].into()
+    }
+
+    fn argc(&self) -> usize {
+        4
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        let ctx = ctx.cast_mut::<GenerateCtx>();
+        let rect = [0, 1, 2, 3].map(|i| *argv[i].cast_ref::<f64>());
+
+        // clips stack up, only what is inside all of them is kept
+        ctx.state.clip = Some(match ctx.state.clip {
+            Some(clip) => intersect_rect(clip, rect),
+            None => rect,
+        });
+
+        vec![]
+    }
+}
+
+fn intersect_rect(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+    let x = a[0].max(b[0]);
+    let y = a[1].max(b[1]);
+    let w = ((a[0] + a[2]).min(b[0] + b[2]) - x).max(0.0);
+    let h = ((a[1] + a[3]).min(b[1] + b[3]) - y).max(0.0);
+    [x, y, w, h]
+}
+
+/// The part of the segment inside the rect (x, y, w, h), Liang-Barsky
+pub fn clip_segment(a: [f64; 2], b: [f64; 2], rect: [f64; 4]) -> Option<([f64; 2], [f64; 2])> {
+    let [x, y, w, h] = rect;
+    let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+
+    let mut t0 = 0.0f64;
+    let mut t1 = 1.0f64;
+    for (p, q) in [
+        (-dx, a[0] - x),
+        (dx, x + w - a[0]),
+        (-dy, a[1] - y),
+        (dy, y + h - a[1]),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+
+    let at = |t: f64| [a[0] + dx * t, a[1] + dy * t];
+    Some((at(t0), at(t1)))
+}
+
+/// Pieces of the polyline inside the rect (x, y, w, h)
+pub fn clip_polyline(points: &[[f64; 2]], rect: [f64; 4]) -> Vec<Vec<[f64; 2]>> {
+    let mut pieces: Vec<Vec<[f64; 2]>> = vec![];
+    for segment in points.windows(2) {
+        if let Some((a, b)) = clip_segment(segment[0], segment[1], rect) {
+            match pieces.last_mut() {
+                Some(piece) if piece.last() == Some(&a) => piece.push(b),
+                _ => pieces.push(vec![a, b]),
+            }
+        }
+    }
+    pieces
+}
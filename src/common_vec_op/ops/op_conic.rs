@@ -0,0 +1,174 @@
+/// Stds
+use std::rc::Rc;
+
+/// 3rds
+use egui_plot::PlotPoint;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::ICommandDescription;
+
+/// Self
+use super::op_quad::quad_to_cubic;
+use super::{Curve, GenerateCtx, VecLineData, process_point};
+
+/// Rational quadratic bezier, the same as SkPath's conic verb
+pub struct CommonOpCONIC;
+
+/// Max times to chop a conic in half when converting it into quads
+const MAX_CONIC_TO_QUAD_POW2: usize = 5;
+
+impl ICommandDescription for CommonOpCONIC {
+    fn name(&self) -> Vec<&str> {
+        ["CONIC"].into()
+    }
+
+    fn argc(&self) -> usize {
+        5
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        let ctx = ctx.cast_mut::<GenerateCtx>();
+        let current_matrix = ctx.state.current_local_trans;
+
+        let w: f64 = *argv[4].cast_ref();
+        let argv = process_point(argv, current_matrix);
+
+        let [x1, y1, x2, y2] = [argv[0], argv[1], argv[2], argv[3]];
+
+        let cursor = ctx.state.cursor;
+        let mut points = Vec::new();
+        let mut t = 0.0;
+        while t < 1.0 {
+            let b0 = (1.0f64 - t).powi(2);
+            let b1 = 2.0 * (1.0 - t) * t * w;
+            let b2 = t.powi(2);
+            let d = b0 + b1 + b2;
+            let x = (b0 * cursor.x + b1 * x1 + b2 * x2) / d;
+            let y = (b0 * cursor.y + b1 * y1 + b2 * y2) / d;
+            points.push(VecLineData::new(x, y));
+            t += 0.01;
+        }
+
+        ctx.curves
+            .push(Curve::Conic([[cursor.x, cursor.y], [x1, y1], [x2, y2]], w));
+        ctx.grouping = true;
+        ctx.state.cursor = PlotPoint::from([x2, y2]);
+
+        AnyData::convert_to_vec(points)
+    }
+}
+
+/// Chop a conic at t = 0.5, both halves share the same new weight
+fn chop_conic(p: [[f64; 2]; 3], w: f64) -> ([[f64; 2]; 3], [[f64; 2]; 3], f64) {
+    let scale = 1.0 / (1.0 + w);
+    let new_w = (0.5 + w * 0.5).sqrt();
+    let wp1 = [p[1][0] * w, p[1][1] * w];
+    let m = [
+        (p[0][0] + 2.0 * wp1[0] + p[2][0]) * scale * 0.5,
+        (p[0][1] + 2.0 * wp1[1] + p[2][1]) * scale * 0.5,
+    ];
+    (
+        [
+            p[0],
+            [(p[0][0] + wp1[0]) * scale, (p[0][1] + wp1[1]) * scale],
+            m,
+        ],
+        [
+            m,
+            [(wp1[0] + p[2][0]) * scale, (wp1[1] + p[2][1]) * scale],
+            p[2],
+        ],
+        new_w,
+    )
+}
+
+/// How many times the conic has to be chopped in half so that treating
+/// every piece as a quad stays within `tol`
+pub fn conic_quad_pow2(p: [[f64; 2]; 3], w: f64, tol: f64) -> usize {
+    let a = w - 1.0;
+    let k = a / (4.0 * (2.0 + a));
+    let x = k * (p[0][0] - 2.0 * p[1][0] + p[2][0]);
+    let y = k * (p[0][1] - 2.0 * p[1][1] + p[2][1]);
+
+    let mut error = (x * x + y * y).sqrt();
+    let mut pow2 = 0;
+    while pow2 < MAX_CONIC_TO_QUAD_POW2 && error > tol {
+        error *= 0.25;
+        pow2 += 1;
+    }
+    pow2
+}
+
+/// Approximate a conic with 2^`pow2` quads, returns the (control, end) points of each quad
+pub fn conic_to_quads(p: [[f64; 2]; 3], w: f64, pow2: usize) -> Vec<[[f64; 2]; 2]> {
+    fn subdivide(p: [[f64; 2]; 3], w: f64, level: usize, quads: &mut Vec<[[f64; 2]; 2]>) {
+        if level == 0 {
+            quads.push([p[1], p[2]]);
+            return;
+        }
+        let (l, r, new_w) = chop_conic(p, w);
+        subdivide(l, new_w, level - 1, quads);
+        subdivide(r, new_w, level - 1, quads);
+    }
+
+    let mut quads = vec![];
+    subdivide(p, w, pow2, &mut quads);
+    quads
+}
+
+/// Approximate a conic with 2^`pow2` cubics, returns the (control1, control2, end) points of each cubic
+pub fn conic_to_cubics(p: [[f64; 2]; 3], w: f64, pow2: usize) -> Vec<[[f64; 2]; 3]> {
+    let mut start = p[0];
+    conic_to_quads(p, w, pow2)
+        .into_iter()
+        .map(|[c, e]| {
+            let cubic = quad_to_cubic([start, c, e]);
+            start = e;
+            cubic
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Farthest the quads get from the conic
+    fn max_error(p: [[f64; 2]; 3], w: f64, quads: &[[[f64; 2]; 2]]) -> f64 {
+        let conic = Curve::Conic(p, w);
+        let mut start = p[0];
+        let mut error = 0f64;
+        for &[c, e] in quads {
+            let quad = Curve::Quad([start, c, e]);
+            for i in 0..=16 {
+                error = error.max(conic.nearest(quad.point(i as f64 / 16.0)).1);
+            }
+            start = e;
+        }
+        error
+    }
+
+    #[test]
+    fn conic_to_quads_keeps_within_tolerance() {
+        let p = [[0.0, 0.0], [100.0, 100.0], [200.0, 0.0]];
+        for w in [0.2, 0.5, std::f64::consts::FRAC_1_SQRT_2, 2.0, 5.0] {
+            for tol in [1.0, 0.1, 0.01] {
+                let quads = conic_to_quads(p, w, conic_quad_pow2(p, w, tol));
+                assert_eq!(quads.last().unwrap()[1], p[2]);
+                // the count is capped, past it the tolerance can't be kept
+                if quads.len() < 1 << MAX_CONIC_TO_QUAD_POW2 {
+                    let error = max_error(p, w, &quads);
+                    assert!(error <= tol, "w {w} tol {tol}: error {error}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn conic_of_weight_one_is_its_quad() {
+        let p = [[0.0, 0.0], [50.0, 80.0], [100.0, 0.0]];
+        assert_eq!(conic_quad_pow2(p, 1.0, 0.01), 0);
+        assert_eq!(conic_to_quads(p, 1.0, 0), vec![[p[1], p[2]]]);
+    }
+}
@@ -9,10 +9,13 @@ use crate::any_data::AnyData;
 use crate::interfaces::ICommandDescription;
 
 /// Self
-use super::{GenerateCtx, VecLineData, process_point};
+use super::{Curve, GenerateCtx, VecLineData, process_point};
 
 pub struct CommonOpCUBI;
 
+/// Most quads a cubic is approximated with
+const MAX_CUBIC_TO_QUADS: usize = 64;
+
 impl ICommandDescription for CommonOpCUBI {
     fn name(&self) -> Vec<&str> {
         ["CUBI", "CUBIC"].into()
@@ -24,13 +27,13 @@ impl ICommandDescription for CommonOpCUBI {
 
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
         let ctx = ctx.cast_mut::<GenerateCtx>();
-        let current_matrix = ctx.current_local_trans;
+        let current_matrix = ctx.state.current_local_trans;
 
         let argv = process_point(argv, current_matrix);
 
         let [x1, y1, x2, y2, x3, y3] = [argv[0], argv[1], argv[2], argv[3], argv[4], argv[5]];
 
-        let cursor = ctx.cursor;
+        let cursor = ctx.state.cursor;
         let mut points = Vec::new();
         let mut t = 0.0;
         while t < 1.0 {
@@ -46,9 +49,100 @@ impl ICommandDescription for CommonOpCUBI {
             t += 0.01;
         }
 
+        ctx.curves.push(Curve::Cubic([
+            [cursor.x, cursor.y],
+            [x1, y1],
+            [x2, y2],
+            [x3, y3],
+        ]));
         ctx.grouping = true;
-        ctx.cursor = PlotPoint::from([x3, y3]);
+        ctx.state.cursor = PlotPoint::from([x3, y3]);
 
         AnyData::convert_to_vec(points)
     }
 }
+
+/// Max distance between a cubic and the quad from the midpoint approximation, sqrt(3) / 36 * |p3 - 3 p2 + 3 p1 - p0|
+fn midpoint_quad_error(p: [[f64; 2]; 4]) -> f64 {
+    let d = [0, 1].map(|i| p[3][i] - 3.0 * p[2][i] + 3.0 * p[1][i] - p[0][i]);
+    3f64.sqrt() / 36.0 * d[0].hypot(d[1])
+}
+
+/// Fewest quads keeping within `tol` of the cubic when it is cut at equal steps of t
+pub fn cubic_quad_count(p: [[f64; 2]; 4], tol: f64) -> usize {
+    // the error of every piece shrinks with the cube of the count
+    let n = (midpoint_quad_error(p) / tol.max(1e-9)).cbrt().ceil() as usize;
+    n.clamp(1, MAX_CUBIC_TO_QUADS)
+}
+
+/// Approximate a cubic with `count` quads, returns the (control, end) points of each quad
+pub fn cubic_to_quads(p: [[f64; 2]; 4], count: usize) -> Vec<[[f64; 2]; 2]> {
+    let point = |t: f64| {
+        let mt = 1.0 - t;
+        [0, 1].map(|i| {
+            mt * mt * mt * p[0][i]
+                + 3.0 * mt * mt * t * p[1][i]
+                + 3.0 * mt * t * t * p[2][i]
+                + t * t * t * p[3][i]
+        })
+    };
+    let derivative = |t: f64| {
+        let mt = 1.0 - t;
+        [0, 1].map(|i| {
+            3.0 * mt * mt * (p[1][i] - p[0][i])
+                + 6.0 * mt * t * (p[2][i] - p[1][i])
+                + 3.0 * t * t * (p[3][i] - p[2][i])
+        })
+    };
+
+    (0..count)
+        .map(|k| {
+            let (t0, t1) = (k as f64 / count as f64, (k + 1) as f64 / count as f64);
+            let h = (t1 - t0) / 3.0;
+            let (p0, p3) = (point(t0), point(t1));
+            let (d0, d3) = (derivative(t0), derivative(t1));
+            let p1 = [p0[0] + d0[0] * h, p0[1] + d0[1] * h];
+            let p2 = [p3[0] - d3[0] * h, p3[1] - d3[1] * h];
+            // average of the controls matching the tangent at either end
+            let c = [0, 1].map(|i| (3.0 * (p1[i] + p2[i]) - p0[i] - p3[i]) / 4.0);
+            [c, p3]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cubic_to_quads_keeps_within_tolerance() {
+        let cubics = [
+            [[0.0, 0.0], [0.0, 100.0], [100.0, 100.0], [100.0, 0.0]],
+            [[0.0, 0.0], [150.0, 80.0], [-50.0, 80.0], [100.0, 0.0]],
+            [[0.0, 0.0], [100.0, 100.0], [0.0, 100.0], [100.0, 0.0]],
+        ];
+        for p in cubics {
+            let cubic = Curve::Cubic(p);
+            for tol in [1.0, 0.1, 0.01] {
+                let count = cubic_quad_count(p, tol);
+                let quads = cubic_to_quads(p, count);
+                assert_eq!(quads.len(), count);
+                assert_eq!(quads.last().unwrap()[1], p[3]);
+
+                let mut start = p[0];
+                for &[c, e] in &quads {
+                    let quad = Curve::Quad([start, c, e]);
+                    for i in 0..=16 {
+                        let (_, error) = cubic.nearest(quad.point(i as f64 / 16.0));
+                        assert!(error <= tol, "{p:?} tol {tol}: error {error}");
+                    }
+                    start = e;
+                }
+                // one quad fewer is past the tolerance unless a single one does
+                if count > 1 {
+                    assert!(midpoint_quad_error(p) / ((count - 1) as f64).powi(3) > tol);
+                }
+            }
+        }
+    }
+}
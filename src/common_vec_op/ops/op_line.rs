@@ -9,7 +9,7 @@ use crate::any_data::AnyData;
 use crate::interfaces::ICommandDescription;
 
 /// Self
-use super::{GenerateCtx, VecLineData, process_point};
+use super::{Curve, GenerateCtx, VecLineData, process_point};
 
 pub struct CommonOpLINE;
 
@@ -24,19 +24,23 @@ impl ICommandDescription for CommonOpLINE {
 
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
         let ctx = ctx.cast_mut::<GenerateCtx>();
-        let current_matrix = ctx.current_local_trans;
+        let current_matrix = ctx.state.current_local_trans;
 
         let argv = process_point(argv, current_matrix);
 
         let nums = [argv[0], argv[1]];
 
         let points = vec![
-            VecLineData::new(ctx.cursor.x, ctx.cursor.y),
+            VecLineData::new(ctx.state.cursor.x, ctx.state.cursor.y),
             VecLineData::new(nums[0], nums[1]),
         ];
 
+        ctx.curves.push(Curve::Line([
+            [ctx.state.cursor.x, ctx.state.cursor.y],
+            nums,
+        ]));
         ctx.grouping = true;
-        ctx.cursor = PlotPoint::from(nums);
+        ctx.state.cursor = PlotPoint::from(nums);
 
         AnyData::convert_to_vec(points)
     }
@@ -25,7 +25,7 @@ impl ICommandDescription for CommonOpMOVE {
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
         let ctx = ctx.cast_mut::<GenerateCtx>();
 
-        let current_matrix = ctx.current_local_trans;
+        let current_matrix = ctx.state.current_local_trans;
 
         let argv = process_point(argv, current_matrix);
         let nums = [argv[0], argv[1]];
@@ -33,7 +33,8 @@ impl ICommandDescription for CommonOpMOVE {
         let points = vec![VecLineData::new(nums[0], nums[1])];
 
         ctx.grouping = false;
-        ctx.cursor = PlotPoint::from(nums);
+        ctx.state.cursor = PlotPoint::from(nums);
+        ctx.state.subpath_start = ctx.state.cursor;
 
         AnyData::convert_to_vec(points)
     }
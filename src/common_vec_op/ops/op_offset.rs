@@ -0,0 +1,75 @@
+/// Stds
+use std::mem::take;
+use std::rc::Rc;
+
+/// 3rds
+use i_overlay::core::fill_rule::FillRule;
+use i_overlay::float::simplify::SimplifyShape;
+use i_overlay::mesh::outline::offset::OutlineOffset;
+use i_overlay::mesh::style::{LineJoin, OutlineStyle};
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::ICommandDescription;
+
+/// Self
+use super::{GenerateCtx, VecLineData};
+
+/// Max angle between the segments of a rounded corner
+const ROUND_JOIN_ANGLE: f64 = 0.1;
+
+/// Draw the outline at distance d of the subpaths drawn since the last OFFSET,
+/// outward if d is positive and inward if negative. Subpaths are taken as closed and filled even-odd
+pub struct CommonOpOFFSET;
+
+impl ICommandDescription for CommonOpOFFSET {
+    fn name(&self) -> Vec<&str> {
+        ["OFFSET"].into()
+    }
+
+    fn argc(&self) -> usize {
+        1
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        let ctx = ctx.cast_mut::<GenerateCtx>();
+        let d: f64 = *argv[0].cast_ref();
+
+        let contours = take(&mut ctx.path)
+            .into_iter()
+            .filter(|c| c.len() > 2)
+            .collect::<Vec<_>>();
+        offset_contours(&contours, d)
+            .into_iter()
+            .for_each(|mut contour| {
+                contour.push(contour[0]);
+                ctx.extra_lines.push(
+                    contour
+                        .into_iter()
+                        .map(|[x, y]| VecLineData::new(x, y))
+                        .collect(),
+                );
+            });
+
+        // the outlines are not a part of the current line
+        ctx.grouping = false;
+
+        vec![]
+    }
+}
+
+/// Contours of the outline at distance d, self-intersections of the input are resolved first
+pub fn offset_contours(contours: &[Vec<[f64; 2]>], d: f64) -> Vec<Vec<[f64; 2]>> {
+    if contours.is_empty() || d == 0.0 {
+        return contours.to_vec();
+    }
+
+    let style = OutlineStyle::new(d).line_join(LineJoin::Round(ROUND_JOIN_ANGLE));
+    contours
+        .simplify_shape(FillRule::EvenOdd)
+        .outline(&style)
+        .into_iter()
+        .flatten()
+        .filter(|c| !c.is_empty())
+        .collect()
+}
@@ -3,10 +3,10 @@ use std::rc::Rc;
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::ICommandDescription;
+use crate::interfaces::{ICommandDescription, StackEffect};
 
 /// Self
-use super::{GenerateCtx, calc_trans_stack};
+use super::operate_stack_effect;
 
 pub struct CommonOpPopTrans;
 
@@ -21,15 +21,12 @@ impl ICommandDescription for CommonOpPopTrans {
         0
     }
 
-    fn operate(&self, ctx: &mut AnyData, _argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
-        if ctx.local_trans_stack.is_empty() {
-            return vec![];
-        }
-        ctx.local_trans_stack.pop();
-        ctx.current_local_trans = calc_trans_stack(&ctx.local_trans_stack);
+    fn stack_effect(&self, _argv: Rc<Vec<AnyData>>) -> StackEffect {
+        StackEffect::PopLocal
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
 
@@ -42,14 +39,11 @@ impl ICommandDescription for CommonOpPopWorldTrans {
         0
     }
 
-    fn operate(&self, ctx: &mut AnyData, _argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
-        if ctx.world_trans_stack.is_empty() {
-            return vec![];
-        }
-        ctx.world_trans_stack.pop();
-        ctx.current_world_trans = calc_trans_stack(&ctx.world_trans_stack);
+    fn stack_effect(&self, _argv: Rc<Vec<AnyData>>) -> StackEffect {
+        StackEffect::PopWorld
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
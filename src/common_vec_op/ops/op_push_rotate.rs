@@ -3,15 +3,28 @@ use std::rc::Rc;
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::ICommandDescription;
+use crate::interfaces::{ICommandDescription, StackEffect};
 
 /// Self
-use super::{GenerateCtx, calc_trans_stack};
+use super::{operate_stack_effect, pivot_matrix, rotate_matrix};
 
 pub struct CommonOpPushRotate;
 
 pub struct CommonOpPushWorldRotate;
 
+pub struct CommonOpPushRotateDeg;
+
+pub struct CommonOpPushWorldRotateDeg;
+
+/// Rotate about the pivot (cx, cy)
+pub struct CommonOpPushRotateAt;
+
+pub struct CommonOpPushWorldRotateAt;
+
+pub struct CommonOpPushRotateDegAt;
+
+pub struct CommonOpPushWorldRotateDegAt;
+
 impl ICommandDescription for CommonOpPushRotate {
     fn name(&self) -> Vec<&str> {
         ["PUSH_ROTATE", "ROTATE"].into()
@@ -21,51 +34,177 @@ impl ICommandDescription for CommonOpPushRotate {
         1
     }
 
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let angle: f64 = *argv[0].cast_ref();
+        let trans_matrix = rotate_matrix(angle);
+
+        StackEffect::PushLocal(trans_matrix)
+    }
+
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
 
+impl ICommandDescription for CommonOpPushWorldRotate {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_WORLD_ROTATE", "WORLD_ROTATE"].into()
+    }
+
+    fn argc(&self) -> usize {
+        1
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
         let angle: f64 = *argv[0].cast_ref();
-        let angle_cos = angle.cos();
-        let angle_sin = angle.sin();
+        let trans_matrix = rotate_matrix(angle);
 
-        let trans_matrix: [[f64; 3]; 3] = [
-            [angle_cos, -angle_sin, 0.0],
-            [angle_sin, angle_cos, 0.0],
-            [0.0, 0.0, 1.0],
-        ];
+        StackEffect::PushWorld(trans_matrix)
+    }
 
-        ctx.local_trans_stack.push(trans_matrix);
-        ctx.current_local_trans = calc_trans_stack(&ctx.local_trans_stack);
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
+
+impl ICommandDescription for CommonOpPushRotateDeg {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_ROTATE_DEG", "ROTATE_DEG"].into()
+    }
+
+    fn argc(&self) -> usize {
+        1
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let angle: f64 = *argv[0].cast_ref();
+        let trans_matrix = rotate_matrix(angle.to_radians());
 
-        vec![]
+        StackEffect::PushLocal(trans_matrix)
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
 
-impl ICommandDescription for CommonOpPushWorldRotate {
+impl ICommandDescription for CommonOpPushWorldRotateDeg {
     fn name(&self) -> Vec<&str> {
-        ["PUSH_WORLD_ROTATE", "WORLD_ROTATE"].into()
+        ["PUSH_WORLD_ROTATE_DEG", "WORLD_ROTATE_DEG"].into()
     }
 
     fn argc(&self) -> usize {
         1
     }
 
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let angle: f64 = *argv[0].cast_ref();
+        let trans_matrix = rotate_matrix(angle.to_radians());
+
+        StackEffect::PushWorld(trans_matrix)
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
+
+impl ICommandDescription for CommonOpPushRotateAt {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_ROTATE_AT", "ROTATE_AT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        3
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let angle: f64 = *argv[0].cast_ref();
+        let trans_matrix = pivot_matrix(
+            rotate_matrix(angle),
+            *argv[1].cast_ref(),
+            *argv[2].cast_ref(),
+        );
+
+        StackEffect::PushLocal(trans_matrix)
+    }
+
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
 
+impl ICommandDescription for CommonOpPushWorldRotateAt {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_WORLD_ROTATE_AT", "WORLD_ROTATE_AT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        3
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
         let angle: f64 = *argv[0].cast_ref();
-        let angle_cos = angle.cos();
-        let angle_sin = angle.sin();
+        let trans_matrix = pivot_matrix(
+            rotate_matrix(angle),
+            *argv[1].cast_ref(),
+            *argv[2].cast_ref(),
+        );
 
-        let trans_matrix: [[f64; 3]; 3] = [
-            [angle_cos, -angle_sin, 0.0],
-            [angle_sin, angle_cos, 0.0],
-            [0.0, 0.0, 1.0],
-        ];
+        StackEffect::PushWorld(trans_matrix)
+    }
 
-        ctx.world_trans_stack.push(trans_matrix);
-        ctx.current_world_trans = calc_trans_stack(&ctx.world_trans_stack);
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
 
-        vec![]
+impl ICommandDescription for CommonOpPushRotateDegAt {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_ROTATE_DEG_AT", "ROTATE_DEG_AT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        3
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let angle: f64 = *argv[0].cast_ref();
+        let trans_matrix = pivot_matrix(
+            rotate_matrix(angle.to_radians()),
+            *argv[1].cast_ref(),
+            *argv[2].cast_ref(),
+        );
+
+        StackEffect::PushLocal(trans_matrix)
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
+
+impl ICommandDescription for CommonOpPushWorldRotateDegAt {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_WORLD_ROTATE_DEG_AT", "WORLD_ROTATE_DEG_AT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        3
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let angle: f64 = *argv[0].cast_ref();
+        let trans_matrix = pivot_matrix(
+            rotate_matrix(angle.to_radians()),
+            *argv[1].cast_ref(),
+            *argv[2].cast_ref(),
+        );
+
+        StackEffect::PushWorld(trans_matrix)
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
@@ -3,15 +3,20 @@ use std::rc::Rc;
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::ICommandDescription;
+use crate::interfaces::{ICommandDescription, StackEffect};
 
 /// Self
-use super::{GenerateCtx, calc_trans_stack};
+use super::{operate_stack_effect, pivot_matrix, scale_matrix};
 
 pub struct CommonOpPushScale;
 
 pub struct CommonOpPushWorldScale;
 
+/// Scale about the pivot (cx, cy)
+pub struct CommonOpPushScaleAt;
+
+pub struct CommonOpPushWorldScaleAt;
+
 impl ICommandDescription for CommonOpPushScale {
     fn name(&self) -> Vec<&str> {
         ["PUSH_SCALE", "SCALE"].into()
@@ -21,18 +26,18 @@ impl ICommandDescription for CommonOpPushScale {
         2
     }
 
-    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
         let trans_matrix: [[f64; 3]; 3] = [
             [*argv[0].cast_ref(), 0.0, 0.0],
             [0.0, *argv[1].cast_ref(), 0.0],
             [0.0, 0.0, 1.0],
         ];
 
-        ctx.local_trans_stack.push(trans_matrix);
-        ctx.current_local_trans = calc_trans_stack(&ctx.local_trans_stack);
+        StackEffect::PushLocal(trans_matrix)
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
 
@@ -45,17 +50,65 @@ impl ICommandDescription for CommonOpPushWorldScale {
         2
     }
 
-    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
         let trans_matrix: [[f64; 3]; 3] = [
             [*argv[0].cast_ref(), 0.0, 0.0],
             [0.0, *argv[1].cast_ref(), 0.0],
             [0.0, 0.0, 1.0],
         ];
 
-        ctx.world_trans_stack.push(trans_matrix);
-        ctx.current_world_trans = calc_trans_stack(&ctx.world_trans_stack);
+        StackEffect::PushWorld(trans_matrix)
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
+
+impl ICommandDescription for CommonOpPushScaleAt {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_SCALE_AT", "SCALE_AT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        4
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let trans_matrix = pivot_matrix(
+            scale_matrix(*argv[0].cast_ref(), *argv[1].cast_ref()),
+            *argv[2].cast_ref(),
+            *argv[3].cast_ref(),
+        );
+
+        StackEffect::PushLocal(trans_matrix)
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
+    }
+}
+
+impl ICommandDescription for CommonOpPushWorldScaleAt {
+    fn name(&self) -> Vec<&str> {
+        ["PUSH_WORLD_SCALE_AT", "WORLD_SCALE_AT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        4
+    }
+
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let trans_matrix = pivot_matrix(
+            scale_matrix(*argv[0].cast_ref(), *argv[1].cast_ref()),
+            *argv[2].cast_ref(),
+            *argv[3].cast_ref(),
+        );
+
+        StackEffect::PushWorld(trans_matrix)
+    }
+
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
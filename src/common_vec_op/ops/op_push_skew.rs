@@ -3,10 +3,10 @@ use std::rc::Rc;
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::ICommandDescription;
+use crate::interfaces::{ICommandDescription, StackEffect};
 
 /// Self
-use super::{GenerateCtx, calc_trans_stack};
+use super::operate_stack_effect;
 
 pub struct CommonOpPushSkew;
 
@@ -21,18 +21,18 @@ impl ICommandDescription for CommonOpPushSkew {
         2
     }
 
-    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
         let trans_matrix: [[f64; 3]; 3] = [
             [1.0, *argv[0].cast_ref(), 0.0],
             [*argv[1].cast_ref(), 1.0, 0.0],
             [0.0, 0.0, 1.0],
         ];
 
-        ctx.local_trans_stack.push(trans_matrix);
-        ctx.current_local_trans = calc_trans_stack(&ctx.local_trans_stack);
+        StackEffect::PushLocal(trans_matrix)
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
 
@@ -45,17 +45,17 @@ impl ICommandDescription for CommonOpPushWorldSkew {
         2
     }
 
-    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
         let trans_matrix: [[f64; 3]; 3] = [
             [1.0, *argv[0].cast_ref(), 0.0],
             [*argv[1].cast_ref(), 1.0, 0.0],
             [0.0, 0.0, 1.0],
         ];
 
-        ctx.world_trans_stack.push(trans_matrix);
-        ctx.current_world_trans = calc_trans_stack(&ctx.world_trans_stack);
+        StackEffect::PushWorld(trans_matrix)
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
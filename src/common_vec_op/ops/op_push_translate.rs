@@ -3,10 +3,10 @@ use std::rc::Rc;
 
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::ICommandDescription;
+use crate::interfaces::{ICommandDescription, StackEffect};
 
 /// Self
-use super::{GenerateCtx, calc_trans_stack};
+use super::{operate_stack_effect, translate_matrix};
 
 pub struct CommonOpPushTranslate;
 
@@ -21,18 +21,14 @@ impl ICommandDescription for CommonOpPushTranslate {
         2
     }
 
-    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
-        let trans_matrix: [[f64; 3]; 3] = [
-            [0.0, 0.0, *argv[0].cast_ref()],
-            [0.0, 0.0, *argv[1].cast_ref()],
-            [0.0, 0.0, 1.0],
-        ];
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let trans_matrix = translate_matrix(*argv[0].cast_ref(), *argv[1].cast_ref());
 
-        ctx.local_trans_stack.push(trans_matrix);
-        ctx.current_local_trans = calc_trans_stack(&ctx.local_trans_stack);
+        StackEffect::PushLocal(trans_matrix)
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
 
@@ -45,17 +41,13 @@ impl ICommandDescription for CommonOpPushWorldTranslate {
         2
     }
 
-    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
-        let ctx = ctx.cast_mut::<GenerateCtx>();
-        let trans_matrix: [[f64; 3]; 3] = [
-            [0.0, 0.0, *argv[0].cast_ref()],
-            [0.0, 0.0, *argv[1].cast_ref()],
-            [0.0, 0.0, 1.0],
-        ];
+    fn stack_effect(&self, argv: Rc<Vec<AnyData>>) -> StackEffect {
+        let trans_matrix = translate_matrix(*argv[0].cast_ref(), *argv[1].cast_ref());
 
-        ctx.world_trans_stack.push(trans_matrix);
-        ctx.current_world_trans = calc_trans_stack(&ctx.world_trans_stack);
+        StackEffect::PushWorld(trans_matrix)
+    }
 
-        vec![]
+    fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        operate_stack_effect(ctx, self.stack_effect(argv))
     }
 }
@@ -9,7 +9,7 @@ use crate::any_data::AnyData;
 use crate::interfaces::ICommandDescription;
 
 /// Self
-use super::{GenerateCtx, VecLineData, process_point};
+use super::{Curve, GenerateCtx, VecLineData, process_point};
 
 pub struct CommonOpQUAD;
 
@@ -24,13 +24,13 @@ impl ICommandDescription for CommonOpQUAD {
 
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
         let ctx = ctx.cast_mut::<GenerateCtx>();
-        let current_matrix = ctx.current_local_trans;
+        let current_matrix = ctx.state.current_local_trans;
 
         let argv = process_point(argv, current_matrix);
 
         let [x1, y1, x2, y2] = [argv[0], argv[1], argv[2], argv[3]];
 
-        let cursor = ctx.cursor;
+        let cursor = ctx.state.cursor;
         let mut points = Vec::new();
         let mut t = 0.0;
         while t < 1.0 {
@@ -40,9 +40,43 @@ impl ICommandDescription for CommonOpQUAD {
             t += 0.01;
         }
 
+        ctx.curves
+            .push(Curve::Quad([[cursor.x, cursor.y], [x1, y1], [x2, y2]]));
         ctx.grouping = true;
-        ctx.cursor = PlotPoint::from([x2, y2]);
+        ctx.state.cursor = PlotPoint::from([x2, y2]);
 
         AnyData::convert_to_vec(points)
     }
 }
+
+/// The cubic drawing exactly the same curve as a quad, returns (control1, control2, end)
+pub fn quad_to_cubic([p0, c, e]: [[f64; 2]; 3]) -> [[f64; 2]; 3] {
+    [
+        [
+            p0[0] + 2.0 / 3.0 * (c[0] - p0[0]),
+            p0[1] + 2.0 / 3.0 * (c[1] - p0[1]),
+        ],
+        [
+            e[0] + 2.0 / 3.0 * (c[0] - e[0]),
+            e[1] + 2.0 / 3.0 * (c[1] - e[1]),
+        ],
+        e,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quad_to_cubic_is_exact() {
+        let quad = [[0.0, 0.0], [30.0, 90.0], [100.0, -20.0]];
+        let [c1, c2, e] = quad_to_cubic(quad);
+        let (quad, cubic) = (Curve::Quad(quad), Curve::Cubic([quad[0], c1, c2, e]));
+        for i in 0..=20 {
+            let t = i as f64 / 20.0;
+            let (a, b) = (quad.point(t), cubic.point(t));
+            assert!((a[0] - b[0]).hypot(a[1] - b[1]) < 1e-9, "t {t}");
+        }
+    }
+}
@@ -0,0 +1,66 @@
+/// Stds
+use std::rc::Rc;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{ICommandDescription, StackEffect};
+
+/// Self
+use super::GenerateCtx;
+
+/// Store the whole graphics state, transforms, cursor and so on
+pub struct CommonOpSave;
+
+/// Bring back the state stored by the last SAVE
+pub struct CommonOpRestore;
+
+impl ICommandDescription for CommonOpSave {
+    fn name(&self) -> Vec<&str> {
+        ["SAVE"].into()
+    }
+
+    fn argc(&self) -> usize {
+        0
+    }
+
+    fn stack_effect(&self, _argv: Rc<Vec<AnyData>>) -> StackEffect {
+        StackEffect::Save
+    }
+
+    fn operate(&self, ctx: &mut AnyData, _argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        let ctx = ctx.cast_mut::<GenerateCtx>();
+        ctx.saved_states.push(ctx.state.clone());
+
+        vec![]
+    }
+}
+
+impl ICommandDescription for CommonOpRestore {
+    fn name(&self) -> Vec<&str> {
+        ["RESTORE"].into()
+    }
+
+    fn argc(&self) -> usize {
+        0
+    }
+
+    fn stack_effect(&self, _argv: Rc<Vec<AnyData>>) -> StackEffect {
+        StackEffect::Restore
+    }
+
+    fn operate(&self, ctx: &mut AnyData, _argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        let ctx = ctx.cast_mut::<GenerateCtx>();
+
+        let Some(state) = ctx.saved_states.pop() else {
+            ctx.diagnostics
+                .push("RESTORE without a matching SAVE".to_owned());
+            return vec![];
+        };
+
+        ctx.state = state;
+        // the cursor may jump, so start a new line
+        ctx.grouping = false;
+
+        vec![]
+    }
+}
@@ -0,0 +1,154 @@
+/// Stds
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+/// 3rds
+use ab_glyph::{Font, FontArc, FontVec, OutlineCurve, Point};
+use eframe::egui::{FontDefinitions, FontFamily};
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{Command, ICommandDescription, ParamType};
+
+/// Self
+use super::op_cubi::CommonOpCUBI;
+use super::op_end::CommonOpEND;
+use super::op_line::CommonOpLINE;
+use super::op_move::CommonOpMOVE;
+use super::op_quad::CommonOpQUAD;
+
+/// Outline of a string, using the fonts bundled in egui
+pub struct CommonOpTEXT;
+
+/// The proportional fonts of egui in fallback order, parsed on first use
+fn fonts() -> &'static [FontArc] {
+    static FONTS: OnceLock<Vec<FontArc>> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let definitions = FontDefinitions::default();
+        definitions.families[&FontFamily::Proportional]
+            .iter()
+            .filter_map(|name| {
+                let data = &definitions.font_data[name];
+                FontVec::try_from_vec_and_index(data.font.to_vec(), data.index)
+                    .ok()
+                    .map(FontArc::new)
+            })
+            .collect()
+    })
+}
+
+impl ICommandDescription for CommonOpTEXT {
+    fn name(&self) -> Vec<&str> {
+        ["TEXT"].into()
+    }
+
+    fn argc(&self) -> usize {
+        4
+    }
+
+    fn param_types(&self) -> Vec<ParamType> {
+        vec![
+            ParamType::Number,
+            ParamType::Number,
+            ParamType::Number,
+            ParamType::String,
+        ]
+    }
+
+    fn operate(&self, _ctx: &mut AnyData, _argv: Rc<Vec<AnyData>>) -> Vec<AnyData> {
+        // everything is done by the expanded ops
+        vec![]
+    }
+
+    fn expand(&self, argv: Rc<Vec<AnyData>>) -> Option<Vec<Command>> {
+        let x: f64 = *argv[0].cast_ref();
+        let y: f64 = *argv[1].cast_ref();
+        let size: f64 = *argv[2].cast_ref();
+        let text: &String = argv[3].cast_ref();
+
+        let fonts = fonts();
+        let main_font = fonts.first()?;
+
+        let mut cmds = vec![];
+        let mut add = |dsc: &'static dyn ICommandDescription, argv: Vec<f64>| {
+            cmds.push(Command {
+                dsc,
+                name: dsc.name()[0],
+                argv: Rc::new(AnyData::convert_to_vec(argv)),
+                span: Default::default(),
+                arg_spans: vec![],
+                timed_args: vec![],
+            });
+        };
+
+        let main_scale = size / main_font.units_per_em().unwrap_or(1.0) as f64;
+        let line_height = main_font.height_unscaled() + main_font.line_gap_unscaled();
+        let line_height = line_height as f64 * main_scale;
+
+        let mut pen = [x, y];
+        let mut last_glyph = None;
+        for c in text.chars() {
+            if c == '\n' {
+                pen = [x, pen[1] - line_height];
+                last_glyph = None;
+                continue;
+            }
+
+            // the first font having the glyph wins, the same as egui does
+            let font_index = fonts
+                .iter()
+                .position(|font| font.glyph_id(c).0 != 0)
+                .unwrap_or(0);
+            let font = &fonts[font_index];
+            let id = font.glyph_id(c);
+            let scale = size / font.units_per_em().unwrap_or(1.0) as f64;
+
+            if let Some((last_index, last_id)) = last_glyph
+                && last_index == font_index
+            {
+                pen[0] += font.kern_unscaled(last_id, id) as f64 * scale;
+            }
+
+            let to_xy = |p: Point| [pen[0] + p.x as f64 * scale, pen[1] + p.y as f64 * scale];
+            if let Some(outline) = font.outline(id) {
+                let mut contour_end = None;
+                for curve in outline.curves {
+                    let (start, end) = match curve {
+                        OutlineCurve::Line(p0, p1) => (p0, p1),
+                        OutlineCurve::Quad(p0, _, p2) => (p0, p2),
+                        OutlineCurve::Cubic(p0, _, _, p3) => (p0, p3),
+                    };
+                    if contour_end != Some(start) {
+                        if contour_end.is_some() {
+                            add(&CommonOpEND {}, vec![]);
+                        }
+                        add(&CommonOpMOVE {}, to_xy(start).into());
+                    }
+                    contour_end = Some(end);
+
+                    match curve {
+                        OutlineCurve::Line(p0, p1) => {
+                            if p0 != p1 {
+                                add(&CommonOpLINE {}, to_xy(p1).into());
+                            }
+                        }
+                        OutlineCurve::Quad(_, p1, p2) => {
+                            add(&CommonOpQUAD {}, [to_xy(p1), to_xy(p2)].concat());
+                        }
+                        OutlineCurve::Cubic(_, p1, p2, p3) => {
+                            add(&CommonOpCUBI {}, [to_xy(p1), to_xy(p2), to_xy(p3)].concat());
+                        }
+                    }
+                }
+                if contour_end.is_some() {
+                    add(&CommonOpEND {}, vec![]);
+                }
+            }
+
+            pen[0] += font.h_advance_unscaled(id) as f64 * scale;
+            last_glyph = Some((font_index, id));
+        }
+
+        Some(cmds)
+    }
+}
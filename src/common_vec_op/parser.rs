@@ -1,11 +1,18 @@
+/// Stds
+use std::rc::Rc;
+
 /// Crates
 use crate::any_data::AnyData;
-use crate::interfaces::{Cursor, IParser, IVisDataGenerator, ParseError};
+use crate::expr::{Expr, TimedArg};
+use crate::interfaces::{Cursor, IParser, IVisDataGenerator, ParamType, ParseError, Span};
 
 pub struct CodeParser<'a> {
     pub code: String,
     pub cursor: Cursor,
 
+    /// count of chars, cursor pos is counted in chars rather than bytes
+    code_len: usize,
+    comments: Vec<Token>,
     generator: &'a mut dyn IVisDataGenerator,
 }
 
@@ -21,6 +28,9 @@ enum CommentType {
 enum TokenValue {
     Ident(String),
     Number(f64),
+    /// a number depending on the time, with the text it is written as
+    Timed(Rc<Expr>, String),
+    Str(String),
     Comment(String),
     Comma,
 }
@@ -33,17 +43,16 @@ impl TokenValue {
         }
     }
 
-    fn into_number(self) -> Result<f64, ParseError> {
+    fn into_any_data(self) -> Result<AnyData, ParseError> {
         match self {
-            TokenValue::Number(f) => Ok(f),
+            TokenValue::Number(f) => Ok(AnyData::new(f)),
+            TokenValue::Timed(e, _) => Ok(AnyData::new(e.eval(0.0))),
+            TokenValue::Str(s) => Ok(AnyData::new(s)),
             _ => Err(ParseError::default()),
         }
     }
 }
 
-// left close right open range [l, r)
-type Span = (Cursor, Cursor);
-
 #[derive(Debug, Clone)]
 struct Token {
     value: TokenValue,
@@ -54,16 +63,19 @@ type ReadResult = Result<Token, ParseError>;
 
 impl<'a> IParser<'a> for CodeParser<'a> {
     fn new(code: AnyData, generator: &'a mut dyn IVisDataGenerator) -> Self {
+        let code = code.cast_ref::<String>().clone();
         Self {
-            code: code.cast_ref::<String>().clone(),
+            code_len: code.chars().count(),
+            code,
             cursor: Cursor::default(),
+            comments: vec![],
             generator,
         }
     }
 
-    fn parse(&'a mut self) -> Result<&'a mut dyn IVisDataGenerator, ParseError> {
+    fn parse(&mut self) -> Result<&mut dyn IVisDataGenerator, ParseError> {
         self.eat_comments()?;
-        while self.curr_pos() < self.code.len() {
+        while self.curr_pos() < self.code_len {
             self.parse_op()?;
         }
         Ok(self.generator)
@@ -71,6 +83,17 @@ impl<'a> IParser<'a> for CodeParser<'a> {
 }
 
 impl CodeParser<'_> {
+    /// Comments met while parsing, with the comment marks
+    pub fn comments(&self) -> Vec<(Span, String)> {
+        self.comments
+            .iter()
+            .filter_map(|t| match &t.value {
+                TokenValue::Comment(c) => Some((t.cursor.clone(), c.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
     fn cursor_next(&mut self, c: char) {
         self.cursor.pos += 1;
         self.cursor.col += 1;
@@ -93,7 +116,7 @@ impl CodeParser<'_> {
     }
 
     fn not_eof(&self) -> bool {
-        self.curr_pos() < self.code.len()
+        self.curr_pos() < self.code_len
     }
 
     fn read_ident(&mut self) -> ReadResult {
@@ -114,36 +137,86 @@ impl CodeParser<'_> {
         })
     }
 
+    /// A number or an expression, up to the comma, the end of the line or a comment
     fn read_number(&mut self) -> ReadResult {
         let cur = self.curr_cur();
-        let mut number = String::new();
-        while self.not_eof() {
-            let c = self.curr_ch();
-            if c == '-' || c.is_numeric() || c == '.' {
-                number.push(c);
-                self.cursor_next(c);
-            } else {
+        let mut chars = self.code.chars().skip(self.curr_pos()).peekable();
+        let mut text = String::new();
+        while let Some(c) = chars.next() {
+            if c == ',' || c == '\n' || (c == '/' && matches!(chars.peek(), Some('/' | '*'))) {
                 break;
             }
+            text.push(c);
         }
-        match number.parse() {
-            Ok(n) => Ok(Token {
-                value: TokenValue::Number(n),
-                cursor: (cur, self.curr_cur()),
-            }),
-            Err(_) => Err(ParseError {
-                msg: format!("Invalid number '{}'", number),
+        let text = text.trim_end().to_owned();
+        text.chars().for_each(|c| self.cursor_next(c));
+
+        let expr = Expr::parse(&text).map_err(|e| ParseError {
+            msg: if text.chars().all(|c| c == '-' || c.is_numeric() || c == '.') {
+                format!("Invalid number '{}'", text)
+            } else {
+                format!("Invalid expression '{}': {}", text, e)
+            },
+            cursor: cur.clone(),
+        })?;
+        Ok(Token {
+            value: if expr.uses_time() {
+                TokenValue::Timed(Rc::new(expr), text)
+            } else {
+                TokenValue::Number(expr.eval(0.0))
+            },
+            cursor: (cur, self.curr_cur()),
+        })
+    }
+
+    fn read_string(&mut self) -> ReadResult {
+        let cur = self.curr_cur();
+        if !self.not_eof() || self.curr_ch() != '"' {
+            return Err(ParseError {
+                msg: "Expected string".to_owned(),
                 cursor: cur,
-            }),
+            });
         }
+        self.cursor_next('"');
+
+        let mut string = String::new();
+        let mut escaping = false;
+        while self.not_eof() {
+            let c = self.curr_ch();
+            self.cursor_next(c);
+            if escaping {
+                string.push(match c {
+                    'n' => '\n',
+                    't' => '\t',
+                    _ => c,
+                });
+                escaping = false;
+            } else if c == '\\' {
+                escaping = true;
+            } else if c == '"' {
+                return Ok(Token {
+                    value: TokenValue::Str(string),
+                    cursor: (cur, self.curr_cur()),
+                });
+            } else {
+                string.push(c);
+            }
+        }
+        Err(ParseError {
+            msg: "Unterminated string".to_owned(),
+            cursor: cur,
+        })
     }
 
-    fn read_n_params(&mut self, n: usize) -> Result<Vec<f64>, ParseError> {
+    fn read_n_params(&mut self, types: Vec<ParamType>) -> Result<Vec<Token>, ParseError> {
         let mut params = Vec::new();
-        for _ in 0..n {
+        for t in types {
             self.eat_comments()?;
-            let number = self.read_number()?.value.into_number()?;
-            params.push(number);
+            let param = match t {
+                ParamType::Number => self.read_number()?,
+                ParamType::String => self.read_string()?,
+            };
+            params.push(param);
             self.eat_comma()?;
         }
         Ok(params)
@@ -218,29 +291,41 @@ impl CodeParser<'_> {
                 self.cursor_next('/');
                 self.cursor_next('*');
 
+                let mut closed = false;
                 while self.not_eof() {
                     if let Some(CommentType::MultiLineEnd) = self.check_comment() {
                         self.cursor_next('*');
                         self.cursor_next('/');
+                        closed = true;
                         break;
                     } else {
                         self.cursor_next(self.curr_ch());
                     }
                 }
+                // a single line comment may end with the code, but not a multi line one
+                if !closed {
+                    return Err(ParseError {
+                        msg: "Invalid comment".to_owned(),
+                        cursor: cur,
+                    });
+                }
             }
             _ => {}
         }
-        if !self.not_eof() && cur.pos != self.curr_pos() {
-            return Err(ParseError {
-                msg: "Invalid comment".to_owned(),
-                cursor: cur,
-            });
-        }
-        let comment = self.code[cur.pos..self.curr_pos()].to_owned();
-        Ok(Token {
+        let comment: String = self
+            .code
+            .chars()
+            .skip(cur.pos)
+            .take(self.curr_pos() - cur.pos)
+            .collect();
+        let token = Token {
             value: TokenValue::Comment(comment),
             cursor: (cur, self.curr_cur()),
-        })
+        };
+        if token.cursor.0.pos != token.cursor.1.pos {
+            self.comments.push(token.clone());
+        }
+        Ok(token)
     }
 
     fn check_comment(&mut self) -> Option<CommentType> {
@@ -248,7 +333,7 @@ impl CodeParser<'_> {
         let mut slash = false;
         let mut asterisk = false;
 
-        while tmp_pos < self.code.len() {
+        while tmp_pos < self.code_len {
             let c = self.code.chars().nth(tmp_pos).unwrap();
             if c == '/' {
                 if slash {
@@ -287,9 +372,31 @@ impl CodeParser<'_> {
             .match_command(ident_string.as_str());
         match cmd {
             Ok(mut cmd) => {
-                let params = self.read_n_params(cmd.dsc.argc())?;
-                let params = AnyData::convert_to_vec(params);
+                let params = self.read_n_params(cmd.dsc.param_types())?;
+                let span_end = params
+                    .last()
+                    .map_or(ident_cur.1.clone(), |t| t.cursor.1.clone());
+                let arg_spans = params.iter().map(|t| t.cursor.clone()).collect();
+                let timed_args = params
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, t)| match &t.value {
+                        TokenValue::Timed(expr, text) => Some(TimedArg {
+                            index,
+                            expr: expr.clone(),
+                            text: text.clone(),
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+                let params = params
+                    .into_iter()
+                    .map(|t| t.value.into_any_data())
+                    .collect::<Result<Vec<_>, _>>()?;
                 cmd.pack(params);
+                cmd.span = (ident_cur.0, span_end);
+                cmd.arg_spans = arg_spans;
+                cmd.timed_args = timed_args;
 
                 self.generator.add(cmd);
                 Ok(())
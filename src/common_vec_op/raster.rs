@@ -0,0 +1,94 @@
+/// 3rds
+use eframe::egui::ColorImage;
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+/// Crates
+use crate::interfaces::IVisData;
+
+/// Stroke the lines into a pixmap, `map` takes every point to its pixel
+fn render(
+    lines: &[Vec<Box<dyn IVisData>>],
+    size: [u32; 2],
+    lcd_coords: bool,
+    [background, color]: [Color; 2],
+    map: impl Fn([f64; 2]) -> [f64; 2],
+) -> Option<Pixmap> {
+    let [w, h] = size;
+    let mut pixmap = Pixmap::new(w.max(1), h.max(1))?;
+    pixmap.fill(background);
+
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    let stroke = Stroke {
+        width: 1.0,
+        ..Default::default()
+    };
+    let transform = if lcd_coords {
+        Transform::identity()
+    } else {
+        Transform::from_row(1.0, 0.0, 0.0, -1.0, 0.0, h as f32)
+    };
+
+    for points in lines {
+        let mut pb = PathBuilder::new();
+        for (i, p) in points.iter().enumerate() {
+            let [x, y] = p.pos();
+            let [x, y] = map([*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]);
+            let [x, y] = [x as f32, y as f32];
+            if i == 0 {
+                pb.move_to(x, y);
+            } else {
+                pb.line_to(x, y);
+            }
+        }
+        if let Some(path) = pb.finish() {
+            pixmap.stroke_path(&path, &paint, &stroke, transform, None);
+        }
+    }
+    Some(pixmap)
+}
+
+/// Draw the lines anti-aliased into a framebuffer of `size` pixels, one unit is one pixel.
+/// The origin is the top left corner in `lcd_coords`, otherwise the bottom left one
+pub fn rasterize(lines: &[Vec<Box<dyn IVisData>>], size: [u32; 2], lcd_coords: bool) -> ColorImage {
+    let [w, h] = size;
+    let Some(pixmap) = render(lines, size, lcd_coords, [Color::BLACK, Color::WHITE], |p| p) else {
+        return ColorImage::new([w as usize, h as usize], Default::default());
+    };
+
+    ColorImage::from_rgba_premultiplied(
+        [pixmap.width() as usize, pixmap.height() as usize],
+        pixmap.data(),
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+/// Like `rasterize`, with the colors given and `map` taking every point to its pixel.
+/// Returns the RGBA bytes without premultiplied alpha
+pub fn rasterize_rgba(
+    lines: &[Vec<Box<dyn IVisData>>],
+    size: [u32; 2],
+    lcd_coords: bool,
+    [background, color]: [[u8; 4]; 2],
+    map: impl Fn([f64; 2]) -> [f64; 2],
+) -> Vec<u8> {
+    let rgba = |[r, g, b, a]: [u8; 4]| Color::from_rgba8(r, g, b, a);
+    let Some(pixmap) = render(
+        lines,
+        size,
+        lcd_coords,
+        [rgba(background), rgba(color)],
+        map,
+    ) else {
+        return vec![0; size[0] as usize * size[1] as usize * 4];
+    };
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect()
+}
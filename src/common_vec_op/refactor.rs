@@ -0,0 +1,218 @@
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{Command, IVisData, IVisDataGenerator, ParseError, Span};
+
+/// Self
+use super::VecLineData;
+use super::convert::{fmt_num_precision, parse_untimed, replace_spans};
+use super::formatter::DEFAULT_FORMAT_PRECISION;
+use super::ops::{GenerateCtx, calc_trans_stack, inverse_matrix, scale_matrix, translate_matrix};
+
+/// Refactorings rewriting the literal coordinates in the code, comments and layout are kept
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CodeTransform {
+    /// Scale and move the drawn path into the box (x, y, w, h), keeping the aspect ratio
+    FitToBox([f64; 4]),
+    /// Move the drawn path so its bounding box starts at (0, 0)
+    TranslateToOrigin,
+    /// Scale the drawn path around (0, 0)
+    Scale(f64),
+    /// Round the coordinates to N decimals
+    Round(usize),
+    /// Snap the coordinates to multiples of the grid size
+    Quantize(f64),
+}
+
+/// Indices of the x of every point in the params of a path op
+fn point_params(op: &Command) -> Vec<usize> {
+    match op.dsc.name()[0] {
+        "MOVE" | "LINE" | "QUAD" | "CUBI" => (0..op.dsc.argc()).step_by(2).collect(),
+        "CONIC" => vec![0, 2],
+        "TEXT" => vec![0],
+        _ => vec![],
+    }
+}
+
+fn transform(p: [f64; 2], m: [[f64; 3]; 3]) -> [f64; 2] {
+    let p = VecLineData::new(p[0], p[1]).matrix(m).cast::<VecLineData>();
+    [p.x(), p.y()]
+}
+
+/// How much the matrix scales lengths, for the sizes in the params
+fn scale(m: [[f64; 3]; 3]) -> f64 {
+    (m[0][0] * m[1][1] - m[0][1] * m[1][0]).abs().sqrt()
+}
+
+/// Bounding box (min x, min y, max x, max y) of everything drawn
+fn bounding_box(generator: &impl IVisDataGenerator) -> Option<[f64; 4]> {
+    generator
+        .generate(0..generator.len() as i64)
+        .iter()
+        // the first line is the origin added by the generator
+        .skip(1)
+        .flatten()
+        .map(|p| {
+            let [x, y] = p.pos();
+            [*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]
+        })
+        .fold(None, |bbox, [x, y]| match bbox {
+            None => Some([x, y, x, y]),
+            Some([x0, y0, x1, y1]) => Some([x0.min(x), y0.min(y), x1.max(x), y1.max(y)]),
+        })
+}
+
+/// Matrix moving and scaling the drawn path, None if the transform works on the literals only
+fn drawn_matrix(transform: &CodeTransform, bbox: Option<[f64; 4]>) -> Option<[[f64; 3]; 3]> {
+    match *transform {
+        CodeTransform::FitToBox([x, y, w, h]) => {
+            let [x0, y0, x1, y1] = bbox?;
+            let (bw, bh) = (x1 - x0, y1 - y0);
+            let s = match (bw > 0.0, bh > 0.0) {
+                (true, true) => (w / bw).min(h / bh),
+                (true, false) => w / bw,
+                (false, true) => h / bh,
+                (false, false) => 1.0,
+            };
+            // center the path in the box
+            let tx = x + (w - bw * s) / 2.0 - x0 * s;
+            let ty = y + (h - bh * s) / 2.0 - y0 * s;
+            Some(calc_trans_stack(&vec![
+                translate_matrix(tx, ty),
+                scale_matrix(s, s),
+            ]))
+        }
+        CodeTransform::TranslateToOrigin => {
+            let [x0, y0, _, _] = bbox?;
+            Some(translate_matrix(-x0, -y0))
+        }
+        CodeTransform::Scale(s) => Some(scale_matrix(s, s)),
+        CodeTransform::Round(_) | CodeTransform::Quantize(_) => None,
+    }
+}
+
+/// Apply the transform to the code by rewriting the params of the path ops, the CLIP rects and
+/// the OFFSET widths. Params under a local or world transform are changed so that the drawn path
+/// moves as told, only params whose value changes are touched
+pub fn transform_code(code: &str, transform: &CodeTransform) -> Result<String, ParseError> {
+    let generator = parse_untimed(code)?;
+    let ops = generator.ops();
+
+    let world_matrix = generator
+        .run(0..ops.len() as i64)
+        .state()
+        .current_world_trans();
+
+    let bbox = match transform {
+        CodeTransform::FitToBox(_) | CodeTransform::TranslateToOrigin => bounding_box(&generator),
+        _ => None,
+    };
+    let drawn = drawn_matrix(transform, bbox);
+
+    let mut edits: Vec<(Span, String)> = vec![];
+    let mut edit = |op: &Command, i: usize, v: f64, precision: usize| {
+        let old = *op.argv[i].cast_ref::<f64>();
+        if (old - v).abs() > 1e-9 {
+            edits.push((op.arg_spans[i].clone(), fmt_num_precision(v, precision)));
+        }
+    };
+
+    let mut gen_ctx = AnyData::new(GenerateCtx::default());
+    for op in ops {
+        let argv = op
+            .argv
+            .iter()
+            .map(|v| v.try_cast_ref::<f64>().copied().unwrap_or_default())
+            .collect::<Vec<_>>();
+
+        match (transform, drawn) {
+            (_, Some(drawn)) => {
+                let local_matrix = gen_ctx
+                    .cast_ref::<GenerateCtx>()
+                    .state()
+                    .current_local_trans();
+                let matrix = calc_trans_stack(&vec![world_matrix, local_matrix]);
+                // go to where the path is drawn, move it there and come back to the params
+                if let Some(inv) = inverse_matrix(matrix) {
+                    let m = calc_trans_stack(&vec![inv, drawn, matrix]);
+                    for i in point_params(op) {
+                        let p = self::transform([argv[i], argv[i + 1]], m);
+                        edit(op, i, p[0], DEFAULT_FORMAT_PRECISION);
+                        edit(op, i + 1, p[1], DEFAULT_FORMAT_PRECISION);
+                    }
+                    if op.dsc.name()[0] == "TEXT" {
+                        edit(op, 2, argv[2] * scale(m), DEFAULT_FORMAT_PRECISION);
+                    }
+                }
+                // the clip rect and the subpaths OFFSET works on are not under the local transforms
+                if let Some(inv) = inverse_matrix(world_matrix) {
+                    let m = calc_trans_stack(&vec![inv, drawn, world_matrix]);
+                    match op.dsc.name()[0] {
+                        "CLIP" => {
+                            let p = self::transform([argv[0], argv[1]], m);
+                            edit(op, 0, p[0], DEFAULT_FORMAT_PRECISION);
+                            edit(op, 1, p[1], DEFAULT_FORMAT_PRECISION);
+                            edit(op, 2, argv[2] * scale(m), DEFAULT_FORMAT_PRECISION);
+                            edit(op, 3, argv[3] * scale(m), DEFAULT_FORMAT_PRECISION);
+                        }
+                        "OFFSET" => edit(op, 0, argv[0] * scale(m), DEFAULT_FORMAT_PRECISION),
+                        _ => {}
+                    }
+                }
+            }
+            (CodeTransform::Round(decimals), None) => {
+                for i in point_params(op) {
+                    let f = 10f64.powi(*decimals as i32);
+                    edit(op, i, (argv[i] * f).round() / f, *decimals);
+                    edit(op, i + 1, (argv[i + 1] * f).round() / f, *decimals);
+                }
+            }
+            (CodeTransform::Quantize(grid), None) if *grid > 0.0 => {
+                for i in point_params(op) {
+                    let snap = |v: f64| (v / grid).round() * grid;
+                    edit(op, i, snap(argv[i]), DEFAULT_FORMAT_PRECISION);
+                    edit(op, i + 1, snap(argv[i + 1]), DEFAULT_FORMAT_PRECISION);
+                }
+            }
+            _ => {}
+        }
+
+        op.expand().iter().for_each(|op| {
+            op.operate(&mut gen_ctx);
+        });
+    }
+
+    Ok(replace_spans(code, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common_vec_op::convert::parse;
+
+    fn drawn(code: &str) -> Vec<[f64; 2]> {
+        let generator = parse(code).unwrap();
+        generator
+            .generate(0..generator.len() as i64)
+            .iter()
+            .flatten()
+            .map(|p| {
+                let [x, y] = p.pos();
+                [*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn scale_moves_clip_and_offset_with_the_path() {
+        let code = "translate, 5, 5,\nclip, 0, 0, 8, 20,\nmove, 0, 0,\nline, 10, 0,\nline, 10, 10,\nline, 0, 0,\noffset, 1,\n";
+        let scaled = transform_code(code, &CodeTransform::Scale(2.0)).unwrap();
+        let (before, after) = (drawn(code), drawn(&scaled));
+        assert_eq!(before.len(), after.len(), "{scaled}");
+        for (a, b) in before.iter().zip(&after) {
+            assert!(
+                (a[0] * 2.0 - b[0]).hypot(a[1] * 2.0 - b[1]) < 1e-6,
+                "{scaled}"
+            );
+        }
+    }
+}
@@ -0,0 +1,153 @@
+/// Crates
+use crate::interfaces::IVisData;
+
+/// Self
+use super::VecLineData;
+
+/// Length of the reveal marker arrow, relative to the size of the drawing
+const ARROW_LENGTH: f64 = 0.08;
+
+/// Lines as far as the pen has drawn them
+pub struct PenReveal {
+    pub lines: Vec<Vec<Box<dyn IVisData>>>,
+    /// where the reveal has got to, and the tip of the arrow showing where it heads
+    pub reveal_marker: Option<[[f64; 2]; 2]>,
+}
+
+fn pos(p: &dyn IVisData) -> [f64; 2] {
+    let [x, y] = p.pos();
+    [*x.cast_ref::<f64>(), *y.cast_ref::<f64>()]
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+/// Cut the lines after the part `fraction` of their whole length, as a pen going
+/// at a steady pace along them in drawing order would leave them
+pub fn reveal_lines(lines: &[Vec<Box<dyn IVisData>>], fraction: f64) -> PenReveal {
+    let points = lines
+        .iter()
+        .map(|line| line.iter().map(|p| pos(p.as_ref())).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let total = points
+        .iter()
+        .flat_map(|line| line.windows(2).map(|w| distance(w[0], w[1])))
+        .sum::<f64>();
+    if total <= 0.0 {
+        return PenReveal {
+            lines: lines.to_vec(),
+            reveal_marker: None,
+        };
+    }
+
+    let [mut min, mut max] = [[f64::INFINITY; 2], [f64::NEG_INFINITY; 2]];
+    for p in points.iter().filter(|line| line.len() > 1).flatten() {
+        min = [min[0].min(p[0]), min[1].min(p[1])];
+        max = [max[0].max(p[0]), max[1].max(p[1])];
+    }
+    let arrow = distance(min, max) * ARROW_LENGTH;
+    // heading along the segment, the point may still be at its start
+    let marker = |p: [f64; 2], [from, to]: [[f64; 2]; 2]| {
+        let len = distance(from, to);
+        let dir = [(to[0] - from[0]) / len, (to[1] - from[1]) / len];
+        [p, [p[0] + dir[0] * arrow, p[1] + dir[1] * arrow]]
+    };
+
+    let mut left = fraction.clamp(0.0, 1.0) * total;
+    let mut res = PenReveal {
+        lines: vec![],
+        reveal_marker: None,
+    };
+    for (line, points) in lines.iter().zip(&points) {
+        let mut shown: Vec<Box<dyn IVisData>> = line.first().into_iter().cloned().collect();
+        for (i, w) in points.windows(2).enumerate() {
+            let len = distance(w[0], w[1]);
+            if len <= 0.0 {
+                shown.push(line[i + 1].clone());
+                continue;
+            }
+            if left < len {
+                let k = left / len;
+                let p = [
+                    w[0][0] + (w[1][0] - w[0][0]) * k,
+                    w[0][1] + (w[1][1] - w[0][1]) * k,
+                ];
+                shown.push(Box::new(VecLineData::new(p[0], p[1])));
+                res.lines.push(shown);
+                res.reveal_marker = Some(marker(p, [w[0], w[1]]));
+                return res;
+            }
+            left -= len;
+            shown.push(line[i + 1].clone());
+            res.reveal_marker = Some(marker(w[1], [w[0], w[1]]));
+        }
+        res.lines.push(shown);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(points: &[&[[f64; 2]]]) -> Vec<Vec<Box<dyn IVisData>>> {
+        points
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|&[x, y]| {
+                        let res: Box<dyn IVisData> = Box::new(VecLineData::new(x, y));
+                        res
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn points(reveal: &PenReveal) -> Vec<Vec<[f64; 2]>> {
+        reveal
+            .lines
+            .iter()
+            .map(|line| line.iter().map(|p| pos(p.as_ref())).collect())
+            .collect()
+    }
+
+    #[test]
+    fn reveal_lines_goes_along_the_length() {
+        // the lone point is the cursor the drawing starts from
+        let drawn = lines(&[&[[0.0, 0.0]], &[[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]]);
+        let arrow = 200f64.sqrt() * ARROW_LENGTH;
+
+        let reveal = reveal_lines(&drawn, 0.0);
+        assert_eq!(points(&reveal), [vec![[0.0, 0.0]], vec![[0.0, 0.0]; 2]]);
+        assert_eq!(reveal.reveal_marker, Some([[0.0, 0.0], [arrow, 0.0]]));
+
+        let reveal = reveal_lines(&drawn, 0.5);
+        assert_eq!(
+            points(&reveal),
+            [vec![[0.0, 0.0]], vec![[0.0, 0.0], [10.0, 0.0], [10.0, 0.0]]]
+        );
+        assert_eq!(reveal.reveal_marker, Some([[10.0, 0.0], [10.0, arrow]]));
+
+        let reveal = reveal_lines(&drawn, 1.0);
+        assert_eq!(
+            points(&reveal),
+            [
+                vec![[0.0, 0.0]],
+                vec![[0.0, 0.0], [10.0, 0.0], [10.0, 10.0]]
+            ]
+        );
+        assert_eq!(
+            reveal.reveal_marker,
+            Some([[10.0, 10.0], [10.0, 10.0 + arrow]])
+        );
+    }
+
+    #[test]
+    fn reveal_lines_without_length_shows_all() {
+        let reveal = reveal_lines(&lines(&[&[[3.0, 4.0]]]), 0.5);
+        assert_eq!(points(&reveal), [vec![[3.0, 4.0]]]);
+        assert_eq!(reveal.reveal_marker, None);
+    }
+}
@@ -0,0 +1,339 @@
+/// Stds
+use std::f64::consts::PI;
+
+/// Crates
+use crate::any_data::AnyData;
+use crate::interfaces::{IVisData, ParseError, Span};
+
+/// Self
+use super::VecLineData;
+use super::convert::{cubic_text, fmt_num, parse_untimed, replace_spans};
+use super::curve::Curve;
+use super::ops::{GenerateCtx, inverse_matrix};
+
+/// Turns sharper than this between two lines are kept as corners when fitting curves
+const CORNER_ANGLE: f64 = PI / 3.0;
+
+/// Times the points are moved along the fitted cubic before it is split
+const REPARAMETERIZE_STEPS: usize = 4;
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn mul(a: [f64; 2], k: f64) -> [f64; 2] {
+    [a[0] * k, a[1] * k]
+}
+
+fn dot(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn distance(a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - b[0]).hypot(a[1] - b[1])
+}
+
+fn normalize(a: [f64; 2]) -> [f64; 2] {
+    let len = a[0].hypot(a[1]);
+    if len > 0.0 { mul(a, 1.0 / len) } else { a }
+}
+
+/// `line`s in a row
+struct LineRun {
+    spans: Vec<Span>,
+    /// from the cursor before the first line, in the coordinates of the params
+    points: Vec<[f64; 2]>,
+}
+
+/// Runs of two or more `line`s in a row
+fn line_runs(code: &str) -> Result<Vec<LineRun>, ParseError> {
+    let generator = parse_untimed(code)?;
+
+    let mut runs = vec![];
+    let mut spans: Vec<Span> = vec![];
+    let mut points = vec![];
+    let mut gen_ctx = AnyData::new(GenerateCtx::default());
+    for op in generator.ops() {
+        if op.dsc.name()[0] == "LINE" {
+            if spans.is_empty() {
+                let ctx = gen_ctx.cast_ref::<GenerateCtx>();
+                // the cursor is already transformed, bring it back to where the params live
+                let Some(inv) = inverse_matrix(ctx.state().current_local_trans()) else {
+                    op.operate(&mut gen_ctx);
+                    continue;
+                };
+                let start = VecLineData::new(ctx.state().cursor().x, ctx.state().cursor().y)
+                    .matrix(inv)
+                    .cast::<VecLineData>();
+                points = vec![[start.x(), start.y()]];
+            }
+            spans.push(op.span.clone());
+            points.push([*op.argv[0].cast_ref(), *op.argv[1].cast_ref()]);
+        } else if !spans.is_empty() {
+            runs.push(LineRun {
+                spans: spans.clone(),
+                points: points.clone(),
+            });
+            spans.clear();
+        }
+        op.expand().iter().for_each(|op| {
+            op.operate(&mut gen_ctx);
+        });
+    }
+    if !spans.is_empty() {
+        runs.push(LineRun { spans, points });
+    }
+
+    Ok(runs.into_iter().filter(|run| run.spans.len() > 1).collect())
+}
+
+/// Put the text of every run at its first `line`, and remove the others
+fn rewrite_runs(
+    code: &str,
+    rewrite: impl Fn(&[[f64; 2]]) -> Vec<String>,
+) -> Result<String, ParseError> {
+    let mut edits = vec![];
+    for LineRun { spans, points } in line_runs(code)? {
+        let text = rewrite(&points);
+        if text.is_empty() || text.len() >= spans.len() {
+            continue;
+        }
+        edits.push((spans[0].clone(), text.join(",\n")));
+        edits.extend(spans[1..].iter().map(|span| (span.clone(), String::new())));
+    }
+    Ok(replace_spans(code, edits))
+}
+
+fn line_text(p: [f64; 2]) -> String {
+    format!("line, {}, {}", fmt_num(p[0]), fmt_num(p[1]))
+}
+
+/// Ramer-Douglas-Peucker, keeps the points of the polyline needed to stay within `tolerance`
+fn rdp(points: &[[f64; 2]], tolerance: f64) -> Vec<[f64; 2]> {
+    fn keep(points: &[[f64; 2]], tolerance: f64, res: &mut Vec<[f64; 2]>) {
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let dir = sub(last, first);
+        let len = dir[0].hypot(dir[1]);
+        let dist = |p: [f64; 2]| {
+            if len > 0.0 {
+                (dir[0] * (p[1] - first[1]) - dir[1] * (p[0] - first[0])).abs() / len
+            } else {
+                distance(p, first)
+            }
+        };
+        let farthest = (1..points.len() - 1)
+            .map(|i| (i, dist(points[i])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match farthest {
+            Some((i, d)) if d > tolerance => {
+                keep(&points[..=i], tolerance, res);
+                keep(&points[i..], tolerance, res);
+            }
+            _ => res.push(last),
+        }
+    }
+
+    let mut res = vec![points[0]];
+    keep(points, tolerance, &mut res);
+    res
+}
+
+/// Simplify every run of `line`s with Ramer-Douglas-Peucker,
+/// the tolerance is in the coordinates of the params
+pub fn simplify_lines(code: &str, tolerance: f64) -> Result<String, ParseError> {
+    rewrite_runs(code, |points| {
+        rdp(points, tolerance)[1..]
+            .iter()
+            .map(|p| line_text(*p))
+            .collect()
+    })
+}
+
+/// Parameters of the points by the distance along the polyline
+fn chord_lengths(points: &[[f64; 2]]) -> Vec<f64> {
+    let mut u = vec![0.0];
+    for w in points.windows(2) {
+        u.push(u.last().unwrap() + distance(w[0], w[1]));
+    }
+    let total = *u.last().unwrap();
+    if total > 0.0 {
+        u.iter_mut().for_each(|v| *v /= total);
+    }
+    u
+}
+
+/// Least squares cubic through the points, with the end tangents fixed
+fn generate_cubic(points: &[[f64; 2]], u: &[f64], tangents: [[f64; 2]; 2]) -> [[f64; 2]; 4] {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let mut c = [[0.0; 2]; 2];
+    let mut x = [0.0; 2];
+    for (p, &t) in points.iter().zip(u) {
+        let mt = 1.0 - t;
+        let b = [mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t];
+        let a = [mul(tangents[0], b[1]), mul(tangents[1], b[2])];
+        c[0][0] += dot(a[0], a[0]);
+        c[0][1] += dot(a[0], a[1]);
+        c[1][1] += dot(a[1], a[1]);
+        let rest = sub(*p, add(mul(first, b[0] + b[1]), mul(last, b[2] + b[3])));
+        x[0] += dot(a[0], rest);
+        x[1] += dot(a[1], rest);
+    }
+    c[1][0] = c[0][1];
+
+    let det = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (mut alpha_l, mut alpha_r) = if det.abs() > 1e-12 {
+        (
+            (x[0] * c[1][1] - x[1] * c[0][1]) / det,
+            (c[0][0] * x[1] - c[1][0] * x[0]) / det,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    // the fit went wrong, fall back to the usual thirds
+    let seg_len = distance(first, last);
+    if alpha_l < 1e-6 * seg_len || alpha_r < 1e-6 * seg_len {
+        alpha_l = seg_len / 3.0;
+        alpha_r = seg_len / 3.0;
+    }
+    [
+        first,
+        add(first, mul(tangents[0], alpha_l)),
+        add(last, mul(tangents[1], alpha_r)),
+        last,
+    ]
+}
+
+/// Farthest point from the cubic and how far it is
+fn max_error(points: &[[f64; 2]], u: &[f64], cubic: &Curve) -> (usize, f64) {
+    (1..points.len() - 1)
+        .map(|i| (i, distance(cubic.point(u[i]), points[i])))
+        .fold(
+            (points.len() / 2, 0.0),
+            |a, b| if b.1 > a.1 { b } else { a },
+        )
+}
+
+/// Move the parameters to where the cubic is nearest to the points, one Newton step
+fn reparameterize(points: &[[f64; 2]], u: &[f64], cubic: &Curve) -> Vec<f64> {
+    points
+        .iter()
+        .zip(u)
+        .map(|(p, &t)| {
+            let diff = sub(cubic.point(t), *p);
+            let (d1, d2) = (cubic.derivative(t), cubic.second_derivative(t));
+            let den = dot(d1, d1) + dot(diff, d2);
+            if den.abs() > 1e-12 {
+                (t - dot(diff, d1) / den).clamp(0.0, 1.0)
+            } else {
+                t
+            }
+        })
+        .collect()
+}
+
+/// Fit cubics through the points, splitting them where the error is the biggest
+fn fit_cubics(
+    points: &[[f64; 2]],
+    tangents: [[f64; 2]; 2],
+    tolerance: f64,
+    res: &mut Vec<[[f64; 2]; 4]>,
+) {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if points.len() == 2 {
+        let d = distance(first, last) / 3.0;
+        res.push([
+            first,
+            add(first, mul(tangents[0], d)),
+            add(last, mul(tangents[1], d)),
+            last,
+        ]);
+        return;
+    }
+
+    let mut u = chord_lengths(points);
+    let mut cubic = generate_cubic(points, &u, tangents);
+    let (mut split, mut error) = max_error(points, &u, &Curve::Cubic(cubic));
+    // close enough to be worth moving the parameters before giving up
+    if error < tolerance * 4.0 {
+        for _ in 0..REPARAMETERIZE_STEPS {
+            if error < tolerance {
+                break;
+            }
+            u = reparameterize(points, &u, &Curve::Cubic(cubic));
+            cubic = generate_cubic(points, &u, tangents);
+            (split, error) = max_error(points, &u, &Curve::Cubic(cubic));
+        }
+    }
+    if error < tolerance {
+        res.push(cubic);
+        return;
+    }
+
+    let center = normalize(sub(points[split - 1], points[split + 1]));
+    fit_cubics(&points[..=split], [tangents[0], center], tolerance, res);
+    fit_cubics(
+        &points[split..],
+        [mul(center, -1.0), tangents[1]],
+        tolerance,
+        res,
+    );
+}
+
+/// Indices of the ends and the corners of the polyline
+fn corners(points: &[[f64; 2]]) -> Vec<usize> {
+    let mut res = vec![0];
+    for i in 1..points.len() - 1 {
+        let (a, b) = (
+            normalize(sub(points[i], points[i - 1])),
+            normalize(sub(points[i + 1], points[i])),
+        );
+        if dot(a, b).clamp(-1.0, 1.0).acos() > CORNER_ANGLE {
+            res.push(i);
+        }
+    }
+    res.push(points.len() - 1);
+    res
+}
+
+/// Commands after a `move` to the first point, fitting `cubi`s through the points.
+/// Corners are kept sharp and straight pieces become `line`s
+pub fn fit_points(points: &[[f64; 2]], tolerance: f64) -> Vec<String> {
+    // repeated points have no direction
+    let mut points = points.to_vec();
+    points.dedup_by(|a, b| distance(*a, *b) < 1e-9);
+    if points.len() < 2 {
+        return vec![];
+    }
+
+    let mut text = vec![];
+    for w in corners(&points).windows(2) {
+        let piece = &points[w[0]..=w[1]];
+        // straight enough for a line
+        if rdp(piece, tolerance).len() == 2 {
+            text.push(line_text(piece[piece.len() - 1]));
+            continue;
+        }
+        let tangents = [
+            normalize(sub(piece[1], piece[0])),
+            normalize(sub(piece[piece.len() - 2], piece[piece.len() - 1])),
+        ];
+        let mut cubics = vec![];
+        fit_cubics(piece, tangents, tolerance, &mut cubics);
+        text.extend(
+            cubics
+                .into_iter()
+                .map(|[_, c1, c2, e]| cubic_text([c1, c2, e])),
+        );
+    }
+    text
+}
+
+/// Fit `cubi`s through every run of `line`s with least squares, corners are kept sharp.
+/// The tolerance is in the coordinates of the params
+pub fn fit_curves(code: &str, tolerance: f64) -> Result<String, ParseError> {
+    rewrite_runs(code, |points| fit_points(points, tolerance))
+}
@@ -1,26 +1,116 @@
 /// Stds
+use std::cell::Cell;
 use std::ops::Neg;
 
 /// 3rds
 use eframe::egui;
 use eframe::egui::Stroke;
-use egui_plot::{Line, LineStyle, Plot};
+use egui_plot::{Arrows, Line, LineStyle, MarkerShape, Plot, Points};
 
 /// Crates
 use crate::interfaces::{IVisData, IVisualizer};
 
 /// Self
 use super::VecLineData;
+use super::analysis::{CurveAnalysis, QuadApproximation};
+use super::ops::inverse_matrix;
+use super::ops::op_clip::clip_polyline;
 
 use crate::COLOR_PALETTE;
 
+/// What the pointer did on the plot, in drawn coordinates
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlotInput {
+    pub clicked: Option<[f64; 2]>,
+    /// where the drag was pressed
+    pub drag_started: Option<[f64; 2]>,
+    pub drag_stopped: Option<[f64; 2]>,
+    pub pointer: Option<[f64; 2]>,
+}
+
 pub struct CommonVecVisualizer {
     t: [[f64; 3]; 3],
+    /// lines drawn thin on top of the input, e.g. the quantized path
+    overlay: Vec<Vec<Box<dyn IVisData>>>,
+    /// x, y, w, h of the screen, lines outside are dimmed
+    canvas: Option<[f64; 4]>,
+    /// curvature combs and marks on the curves
+    analysis: Option<CurveAnalysis>,
+    /// points where the paths cross
+    intersections: Vec<[f64; 2]>,
+    /// quads standing in for the cubics, with their error
+    approximation: Option<QuadApproximation>,
+    /// tested point and the nearest point on the path
+    hit: Option<([f64; 2], Option<[f64; 2]>)>,
+    /// lines of the drawing in progress, e.g. the handles of the pen
+    guides: Vec<Vec<[f64; 2]>>,
+    /// where the reveal of the path has got to, and the tip of its direction arrow
+    reveal_marker: Option<[[f64; 2]; 2]>,
+    /// what the pointer did during the last `plot`
+    input: Cell<PlotInput>,
+}
+
+impl CommonVecVisualizer {
+    pub fn with_overlay(mut self, overlay: Vec<Vec<Box<dyn IVisData>>>) -> Self {
+        self.overlay = overlay;
+        self
+    }
+
+    pub fn with_canvas(mut self, canvas: Option<[f64; 4]>) -> Self {
+        self.canvas = canvas;
+        self
+    }
+
+    pub fn with_analysis(mut self, analysis: Option<CurveAnalysis>) -> Self {
+        self.analysis = analysis;
+        self
+    }
+
+    pub fn with_intersections(mut self, intersections: Vec<[f64; 2]>) -> Self {
+        self.intersections = intersections;
+        self
+    }
+
+    pub fn with_approximation(mut self, approximation: Option<QuadApproximation>) -> Self {
+        self.approximation = approximation;
+        self
+    }
+
+    pub fn with_hit(mut self, hit: Option<([f64; 2], Option<[f64; 2]>)>) -> Self {
+        self.hit = hit;
+        self
+    }
+
+    pub fn with_reveal_marker(mut self, reveal_marker: Option<[[f64; 2]; 2]>) -> Self {
+        self.reveal_marker = reveal_marker;
+        self
+    }
+
+    pub fn with_guides(mut self, guides: Vec<Vec<[f64; 2]>>) -> Self {
+        self.guides = guides;
+        self
+    }
+
+    /// What the pointer did during the last `plot`
+    pub fn input(&self) -> PlotInput {
+        self.input.get()
+    }
 }
 
 impl IVisualizer for CommonVecVisualizer {
     fn new(transform: [[f64; 3]; 3]) -> Self {
-        Self { t: transform }
+        Self {
+            t: transform,
+            overlay: vec![],
+            canvas: None,
+            analysis: None,
+            intersections: vec![],
+            approximation: None,
+            hit: None,
+            reveal_marker: None,
+            guides: vec![],
+            input: Cell::new(PlotInput::default()),
+        }
     }
 
     fn plot(
@@ -56,6 +146,10 @@ impl IVisualizer for CommonVecVisualizer {
                 if points.is_empty() {
                     continue;
                 }
+                let raw_points = points
+                    .iter()
+                    .map(|v| [*v.pos()[0].cast_ref(), *v.pos()[1].cast_ref()])
+                    .collect::<Vec<[f64; 2]>>();
                 let points = points
                     .into_iter()
                     .map(|v| v.matrix(trans_matrix).cast())
@@ -83,17 +177,238 @@ impl IVisualizer for CommonVecVisualizer {
                     .into_iter()
                     .map(|v| [*v.pos()[0].cast_ref(), *v.pos()[1].cast_ref()])
                     .collect();
-                let drawn_lines = Line::new(points);
-                plot_ui.line(if has_error {
-                    drawn_lines.color(egui::Color32::DARK_RED).width(5.0)
+                let (color, width) = if has_error {
+                    (egui::Color32::DARK_RED, 5.0)
                 } else {
-                    drawn_lines.color(COLOR_PALETTE[color_index]).width(2.0)
-                });
+                    (COLOR_PALETTE[color_index], 2.0)
+                };
+                match self.canvas {
+                    Some(canvas) => {
+                        // dim everything, then draw the parts on the canvas again
+                        plot_ui.line(
+                            Line::new(points)
+                                .color(color.gamma_multiply(0.25))
+                                .width(width),
+                        );
+                        for piece in clip_polyline(&raw_points, canvas) {
+                            let piece = piece
+                                .into_iter()
+                                .map(|[x, y]| transform_point([x, y], trans_matrix))
+                                .collect::<Vec<_>>();
+                            plot_ui.line(Line::new(piece).color(color).width(width));
+                        }
+                    }
+                    None => plot_ui.line(Line::new(points).color(color).width(width)),
+                }
 
                 if colorful_block {
                     color_index = (color_index + 1) % COLOR_PALETTE.len();
                 }
             }
+
+            if let Some([x, y, w, h]) = self.canvas {
+                let frame = [[x, y], [x + w, y], [x + w, y + h], [x, y + h], [x, y]]
+                    .map(|p| transform_point(p, trans_matrix));
+                plot_ui.line(
+                    Line::new(frame.to_vec())
+                        .color(egui::Color32::GRAY)
+                        .width(1.0),
+                );
+            }
+
+            for points in &self.overlay {
+                let points: Vec<[f64; 2]> = points
+                    .iter()
+                    .map(|v| {
+                        transform_point(
+                            [*v.pos()[0].cast_ref(), *v.pos()[1].cast_ref()],
+                            trans_matrix,
+                        )
+                    })
+                    .collect();
+                plot_ui.line(Line::new(points).color(egui::Color32::RED).width(1.0));
+            }
+
+            if let Some(analysis) = &self.analysis {
+                let t = |p: [f64; 2]| transform_point(p, trans_matrix);
+                let comb_color = egui::Color32::from_rgb(0x4f, 0x9d, 0xde);
+                for tooth in &analysis.teeth {
+                    plot_ui.line(
+                        Line::new(tooth.map(t).to_vec())
+                            .color(comb_color.gamma_multiply(0.5))
+                            .width(1.0),
+                    );
+                }
+                for comb in &analysis.combs {
+                    plot_ui.line(
+                        Line::new(comb.iter().map(|p| t(*p)).collect::<Vec<_>>())
+                            .color(comb_color)
+                            .width(1.0),
+                    );
+                }
+                let marks = [
+                    (
+                        "Extrema",
+                        &analysis.extrema,
+                        MarkerShape::Square,
+                        egui::Color32::GOLD,
+                    ),
+                    (
+                        "Inflections",
+                        &analysis.inflections,
+                        MarkerShape::Diamond,
+                        egui::Color32::LIGHT_GREEN,
+                    ),
+                    (
+                        "Cusps",
+                        &analysis.cusps,
+                        MarkerShape::Cross,
+                        egui::Color32::RED,
+                    ),
+                ];
+                for (name, points, shape, color) in marks {
+                    plot_ui.points(
+                        Points::new(points.iter().map(|p| t(*p)).collect::<Vec<_>>())
+                            .name(name)
+                            .shape(shape)
+                            .color(color)
+                            .filled(true)
+                            .radius(4.0),
+                    );
+                }
+                let arrows = [
+                    ("Tangent", &analysis.tangents, egui::Color32::LIGHT_BLUE),
+                    (
+                        "Normal",
+                        &analysis.normals,
+                        egui::Color32::from_rgb(0xe0, 0x6c, 0xf0),
+                    ),
+                ];
+                for (name, vectors, color) in arrows {
+                    plot_ui.arrows(
+                        Arrows::new(
+                            vectors.iter().map(|v| t(v[0])).collect::<Vec<_>>(),
+                            vectors.iter().map(|v| t(v[1])).collect::<Vec<_>>(),
+                        )
+                        .name(name)
+                        .color(color)
+                        .tip_length(8.0),
+                    );
+                }
+            }
+
+            if let Some(approximation) = &self.approximation {
+                let color = egui::Color32::from_rgb(0xff, 0xa5, 0x00);
+                for error in &approximation.errors {
+                    plot_ui.line(
+                        Line::new(error.map(|p| transform_point(p, trans_matrix)).to_vec())
+                            .color(color.gamma_multiply(0.5))
+                            .width(1.0),
+                    );
+                }
+                for quad in &approximation.quads {
+                    plot_ui.line(
+                        Line::new(
+                            quad.iter()
+                                .map(|p| transform_point(*p, trans_matrix))
+                                .collect::<Vec<_>>(),
+                        )
+                        .color(color)
+                        .style(LineStyle::dashed_dense())
+                        .width(1.0),
+                    );
+                }
+            }
+
+            if !self.intersections.is_empty() {
+                plot_ui.points(
+                    Points::new(
+                        self.intersections
+                            .iter()
+                            .map(|p| transform_point(*p, trans_matrix))
+                            .collect::<Vec<_>>(),
+                    )
+                    .name("Intersections")
+                    .shape(MarkerShape::Circle)
+                    .color(egui::Color32::RED)
+                    .radius(5.0),
+                );
+            }
+
+            if let Some((point, nearest)) = self.hit {
+                let point = transform_point(point, trans_matrix);
+                if let Some(nearest) = nearest {
+                    let nearest = transform_point(nearest, trans_matrix);
+                    plot_ui.line(
+                        Line::new(vec![point, nearest])
+                            .color(egui::Color32::YELLOW)
+                            .style(LineStyle::dashed_dense())
+                            .width(1.0),
+                    );
+                    plot_ui.points(
+                        Points::new(vec![nearest])
+                            .name("Nearest")
+                            .color(egui::Color32::YELLOW)
+                            .radius(4.0),
+                    );
+                }
+                plot_ui.points(
+                    Points::new(vec![point])
+                        .name("Hit Test")
+                        .shape(MarkerShape::Plus)
+                        .color(egui::Color32::YELLOW)
+                        .radius(6.0),
+                );
+            }
+
+            if let Some(marker) = self.reveal_marker {
+                let [point, tip] = marker.map(|p| transform_point(p, trans_matrix));
+                let color = egui::Color32::from_rgb(0xff, 0x63, 0x47);
+                plot_ui.arrows(
+                    Arrows::new(vec![point], vec![tip])
+                        .name("Reveal")
+                        .color(color)
+                        .tip_length(10.0),
+                );
+                plot_ui.points(
+                    Points::new(vec![point])
+                        .name("Reveal")
+                        .shape(MarkerShape::Circle)
+                        .filled(true)
+                        .color(color)
+                        .radius(5.0),
+                );
+            }
+
+            for guide in &self.guides {
+                plot_ui.line(
+                    Line::new(
+                        guide
+                            .iter()
+                            .map(|p| transform_point(*p, trans_matrix))
+                            .collect::<Vec<_>>(),
+                    )
+                    .color(egui::Color32::YELLOW)
+                    .width(1.0),
+                );
+            }
+
+            let Some(inv) = inverse_matrix(trans_matrix) else {
+                return;
+            };
+            let to_drawn = |p: egui_plot::PlotPoint| transform_point([p.x, p.y], inv);
+            let pointer = plot_ui.pointer_coordinate().map(to_drawn);
+            let response = plot_ui.response();
+            let press_origin = plot_ui
+                .ctx()
+                .input(|i| i.pointer.press_origin())
+                .map(|pos| to_drawn(plot_ui.plot_from_screen(pos)));
+            self.input.set(PlotInput {
+                clicked: pointer.filter(|_| response.clicked()),
+                drag_started: press_origin.filter(|_| response.drag_started()),
+                drag_stopped: pointer.filter(|_| response.drag_stopped()),
+                pointer,
+            });
         });
     }
 
@@ -101,3 +416,10 @@ impl IVisualizer for CommonVecVisualizer {
         self.t = matrix;
     }
 }
+
+fn transform_point(p: [f64; 2], matrix: [[f64; 3]; 3]) -> [f64; 2] {
+    let p = VecLineData::new(p[0], p[1])
+        .matrix(matrix)
+        .cast::<VecLineData>();
+    [p.x(), p.y()]
+}
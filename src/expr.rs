@@ -0,0 +1,221 @@
+use std::f64::consts::{PI, TAU};
+use std::rc::Rc;
+
+/// Arithmetic written in place of a number param, `t` is the time in seconds
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Num(f64),
+    Time,
+    Neg(Box<Expr>),
+    /// one of + - * / % ^
+    Bin(char, Box<Expr>, Box<Expr>),
+    Call(Function, Box<Expr>),
+}
+
+/// A param depending on the time, evaluated again for every frame
+#[derive(Debug, Clone)]
+pub struct TimedArg {
+    pub index: usize,
+    pub expr: Rc<Expr>,
+    /// as written in the code
+    pub text: String,
+}
+
+type Function = fn(f64) -> f64;
+
+const FUNCTIONS: [(&str, Function); 9] = [
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+    ("abs", f64::abs),
+    ("sqrt", f64::sqrt),
+    ("floor", f64::floor),
+    ("ceil", f64::ceil),
+    ("round", f64::round),
+    ("fract", f64::fract),
+];
+
+impl Expr {
+    pub fn eval(&self, t: f64) -> f64 {
+        match self {
+            Expr::Num(v) => *v,
+            Expr::Time => t,
+            Expr::Neg(e) => -e.eval(t),
+            Expr::Bin(op, a, b) => {
+                let (a, b) = (a.eval(t), b.eval(t));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    '%' => a.rem_euclid(b),
+                    _ => a.powf(b),
+                }
+            }
+            Expr::Call(f, e) => f(e.eval(t)),
+        }
+    }
+
+    pub fn uses_time(&self) -> bool {
+        match self {
+            Expr::Num(_) => false,
+            Expr::Time => true,
+            Expr::Neg(e) | Expr::Call(_, e) => e.uses_time(),
+            Expr::Bin(_, a, b) => a.uses_time() || b.uses_time(),
+        }
+    }
+
+    /// Parse numbers, `t`, `pi`, `tau`, + - * / % ^, parentheses and the functions
+    /// sin cos tan abs sqrt floor ceil round fract
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let mut parser = ExprParser {
+            chars: text.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.sum()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("Unexpected '{c}'")),
+        }
+    }
+}
+
+struct ExprParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// The next char after whitespace if it is one of `ops`
+    fn eat(&mut self, ops: &str) -> Option<char> {
+        self.skip_whitespace();
+        let c = self.peek().filter(|c| ops.contains(*c))?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.product()?;
+        while let Some(op) = self.eat("+-") {
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.product()?));
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.eat("*/%") {
+            lhs = Expr::Bin(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-").is_some() {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.atom()?;
+        // right associative, and binds tighter than the minus on its left
+        if self.eat("^").is_some() {
+            return Ok(Expr::Bin('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                self.eat(")").ok_or("Expected ')'")?;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let number = self.chars[start..self.pos].iter().collect::<String>();
+                number
+                    .parse()
+                    .map(Expr::Num)
+                    .map_err(|_| format!("Invalid number '{number}'"))
+            }
+            Some(c) if c.is_alphabetic() => {
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                    self.pos += 1;
+                }
+                let name = self.chars[start..self.pos]
+                    .iter()
+                    .collect::<String>()
+                    .to_lowercase();
+                match name.as_str() {
+                    "t" => Ok(Expr::Time),
+                    "pi" => Ok(Expr::Num(PI)),
+                    "tau" => Ok(Expr::Num(TAU)),
+                    _ => {
+                        let (_, f) = FUNCTIONS
+                            .iter()
+                            .find(|(n, _)| *n == name)
+                            .ok_or(format!("Unknown name '{name}'"))?;
+                        self.eat("(")
+                            .ok_or(format!("Expected '(' after '{name}'"))?;
+                        let arg = self.sum()?;
+                        self.eat(")").ok_or("Expected ')'")?;
+                        Ok(Expr::Call(*f, Box::new(arg)))
+                    }
+                }
+            }
+            Some(c) => Err(format!("Unexpected '{c}'")),
+            None => Err("Missing value".to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, t: f64) -> f64 {
+        Expr::parse(text).unwrap().eval(t)
+    }
+
+    #[test]
+    fn expr_follows_precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(eval("10 - 4 - 3", 0.0), 3.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0.0), 512.0);
+        assert_eq!(eval("-2 ^ 2", 0.0), -4.0);
+        assert_eq!(eval("-7 % 3", 0.0), 2.0);
+    }
+
+    #[test]
+    fn expr_evaluates_time_and_functions() {
+        assert!(!Expr::parse("pi / 2").unwrap().uses_time());
+        assert!(Expr::parse("sin(T)").unwrap().uses_time());
+        assert_eq!(eval("t * 2 + 1", 1.5), 4.0);
+        assert_eq!(eval("floor(t) + fract(t)", 2.25), 2.25);
+        assert!((eval("cos(tau * t)", 0.5) + 1.0).abs() < 1e-12);
+        assert_eq!(eval("sqrt(abs(-16))", 0.0), 4.0);
+    }
+
+    #[test]
+    fn expr_rejects_bad_text() {
+        for text in ["", "1 +", "(1", "1 2", "foo(1)", "sin 1", "1..2"] {
+            assert!(Expr::parse(text).is_err(), "{text:?}");
+        }
+    }
+}
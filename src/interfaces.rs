@@ -1,4 +1,5 @@
 use crate::any_data::AnyData;
+use crate::expr::TimedArg;
 use dyn_clone::DynClone;
 use eframe::egui;
 use egui_code_editor::Syntax;
@@ -8,17 +9,58 @@ use std::fmt::{Debug, Formatter};
 use std::ops::Range;
 use std::rc::Rc;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamType {
+    Number,
+    String,
+}
+
+/// What an op does to the transform stacks and the saved states
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackEffect {
+    PushLocal([[f64; 3]; 3]),
+    PushWorld([[f64; 3]; 3]),
+    PopLocal,
+    PopWorld,
+    Save,
+    Restore,
+    None,
+}
+
 pub trait ICommandDescription {
     fn name(&self) -> Vec<&str>;
     fn argc(&self) -> usize;
 
+    /// Type of each param, all numbers by default
+    fn param_types(&self) -> Vec<ParamType> {
+        vec![ParamType::Number; self.argc()]
+    }
+
+    /// What the op pushes or pops, the stacks are linted with it
+    fn stack_effect(&self, _argv: Rc<Vec<AnyData>>) -> StackEffect {
+        StackEffect::None
+    }
+
     fn operate(&self, ctx: &mut AnyData, argv: Rc<Vec<AnyData>>) -> Vec<AnyData>;
+
+    /// Ops made of other ops, like a text made of glyph outlines, are expanded before operating
+    fn expand(&self, _argv: Rc<Vec<AnyData>>) -> Option<Vec<Command>> {
+        None
+    }
 }
 
 #[derive(Clone)]
 pub struct Command {
     pub dsc: &'static dyn ICommandDescription,
+    /// One of the names of the description, the one the command is written with
+    pub name: &'static str,
     pub argv: Rc<Vec<AnyData>>,
+    /// Where the command is written in the code, from the op name to the last param
+    pub span: Span,
+    /// Where each param is written in the code
+    pub arg_spans: Vec<Span>,
+    /// Params written with the time `t`, their values in argv are for the time set last
+    pub timed_args: Vec<TimedArg>,
 }
 
 impl Command {
@@ -28,9 +70,43 @@ impl Command {
         self.argv = Rc::new(argv);
     }
 
+    pub fn is_timed(&self) -> bool {
+        !self.timed_args.is_empty()
+    }
+
+    /// Evaluate the params depending on the time again
+    pub fn set_time(&mut self, t: f64) {
+        if !self.is_timed() {
+            return;
+        }
+        let mut argv = self
+            .argv
+            .iter()
+            .map(|v| match v.try_cast_ref::<f64>() {
+                Some(v) => AnyData::new(*v),
+                None => v.clone::<String>(),
+            })
+            .collect::<Vec<_>>();
+        for arg in &self.timed_args {
+            argv[arg.index] = AnyData::new(arg.expr.eval(t));
+        }
+        self.argv = Rc::new(argv);
+    }
+
     pub fn operate(&self, ctx: &mut AnyData) -> Vec<AnyData> {
         self.dsc.operate(ctx, self.argv.clone())
     }
+
+    /// The ops to operate in place of this one, expanded ops keep the span of this one
+    pub fn expand(&self) -> Vec<Command> {
+        match self.dsc.expand(self.argv.clone()) {
+            Some(mut cmds) => {
+                cmds.iter_mut().for_each(|cmd| cmd.span = self.span.clone());
+                cmds
+            }
+            None => vec![self.clone()],
+        }
+    }
 }
 
 impl Debug for Command {
@@ -39,13 +115,16 @@ impl Debug for Command {
     }
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct Cursor {
     pub row: usize,
     pub col: usize,
     pub pos: usize,
 }
 
+// left close right open range [l, r)
+pub type Span = (Cursor, Cursor);
+
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub msg: String,
@@ -61,6 +140,29 @@ impl Default for ParseError {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+}
+
+/// Rewrites of the code which make a diagnostic go away
+#[derive(Debug, Clone)]
+pub struct QuickFix {
+    pub title: String,
+    /// An empty text removes the whole command along with its trailing comma
+    pub edits: Vec<(Span, String)>,
+}
+
+/// Problems found in code which parses fine, they don't stop the generation
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub level: DiagnosticLevel,
+    pub msg: String,
+    pub span: Span,
+    pub fix: Option<QuickFix>,
+}
+
 pub trait ICommandSyntax {
     fn name(&self) -> &'static str;
 
@@ -99,10 +201,14 @@ pub trait ICommandSyntax {
         };
         let cmd = cmd.as_str();
         for desc in self.formats() {
-            if desc.name().contains(&cmd) {
+            if let Some(name) = desc.name().into_iter().find(|name| *name == cmd) {
                 return Ok(Command {
                     dsc: desc,
+                    name,
                     argv: Rc::new(vec![]),
+                    span: Default::default(),
+                    arg_spans: vec![],
+                    timed_args: vec![],
                 });
             }
         }
@@ -125,7 +231,7 @@ pub trait ICommandSyntax {
 pub trait IParser<'a> {
     fn new(code: AnyData, generator: &'a mut dyn IVisDataGenerator) -> Self;
 
-    fn parse(&'a mut self) -> Result<&'a mut dyn IVisDataGenerator, ParseError>;
+    fn parse(&mut self) -> Result<&mut dyn IVisDataGenerator, ParseError>;
 }
 
 #[allow(dead_code)]
@@ -142,6 +248,10 @@ pub trait IVisDataGenerator {
 
     fn generate(&self, range: Range<i64>) -> Vec<Vec<Box<dyn IVisData>>>;
 
+    /// Run all ops and collect what they complain about,
+    /// points outside the `canvas` (x, y, w, h) are reported if it is given
+    fn diagnose(&self, canvas: Option<[f64; 4]>) -> Vec<Diagnostic>;
+
     fn len(&self) -> usize;
 
     fn command_syntax(&self) -> &'static dyn ICommandSyntax;
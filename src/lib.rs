@@ -1,11 +1,16 @@
 mod any_data;
 mod app;
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
 mod common_vec_op;
 mod cus_component;
+mod expr;
 mod interfaces;
 mod sample_codes_list;
 
 pub use app::MainApp;
+#[cfg(not(target_arch = "wasm32"))]
+pub use cli::run_cli;
 pub use eframe::egui;
 
 // beautiful colors
@@ -5,6 +5,11 @@ use vegravis::MainApp;
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> Result<(), eframe::Error> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(code) = vegravis::run_cli(&args) {
+        std::process::exit(code);
+    }
+
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default().with_icon(